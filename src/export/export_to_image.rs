@@ -1,15 +1,15 @@
 use image::{Rgb, RgbImage};
 use robotics_lib::world::tile::{Content, Tile, TileType};
 
-    pub fn export_to_image(filename: &str, map: &Vec<Vec<Tile>>)->Result<(), String> {
-        let (width, height) = (map.len(), map.len());
+    pub fn export_to_image(filename: &str, map: &[Vec<Tile>])->Result<(), String> {
+        let (width, height) = (map.len(), map.first().map_or(0, |column| column.len()));
 
 
         let mut image = RgbImage::new(width as u32, height as u32);
 
-        for x in 0..width {
-            for y in 0..height {
-                let tile_color = color_for_tile(&map[x][y]);
+        for (x, column) in map.iter().enumerate() {
+            for (y, tile) in column.iter().enumerate().take(height) {
+                let tile_color = color_for_tile(tile);
                 image.put_pixel(x as u32, y as u32, tile_color);
             }
         }
@@ -1,8 +1,13 @@
+#[cfg(test)]
 use export::{export_to_file::{export_to_file, import_from_file}, export_to_image::export_to_image};
-use robotics_lib::{event::events::Event, world::world_generator::Generator};
+#[cfg(test)]
+use robotics_lib::world::world_generator::Generator;
+#[cfg(test)]
 use worldgen::{generator::WorldGenerator, noise_bundle::{NoiseBundle}};
 
 pub mod utils;
+// Debug exporters of the generated worlds, only the tests use them
+#[cfg(test)]
 pub mod export;
 pub mod worldgen;
 
@@ -1,127 +1,17 @@
-use std::cmp::Ordering;
-use std::ops::{Bound, Mul};
-use noise::{Abs, Add, Checkerboard, Clamp, Cylinders, Multiply, NoiseFn, Perlin, PerlinSurflet, Simplex, SuperSimplex};
+use noise::{
+    Abs, Add, Billow, Blend, Clamp, Constant, Exponent, Fbm, Max, Min, Multiply, NoiseFn, Perlin, Power, RidgedMulti,
+    ScaleBias, Select, Simplex, Turbulence, Worley,
+};
 use rand::{Rng, thread_rng};
 
-trait Bounded<T,const DIM:usize> :NoiseFn<T,DIM> {
-    fn min(&self)->T;
-    fn max(&self)->T;
-
-}
-
-impl Bounded<f64,2> for Perlin{
-    fn min(&self) -> f64 {
-        -1.
-    }
-
-    fn max(&self) -> f64 {
-        1.
-    }
-}
-impl Bounded<f64,2> for PerlinSurflet{
-    fn min(&self) -> f64 {
-        -1.
-    }
-
-    fn max(&self) -> f64 {
-        1.
-    }
-}
-impl  Bounded<f64,2> for Simplex{
-    fn min(&self) -> f64 {
-        -1.
-    }
-
-    fn max(&self) -> f64 {
-        1.
-    }
-}
-impl Bounded<f64,2> for SuperSimplex{
-    fn min(&self)->f64{
-        -1.
-    }
-    fn max(&self)->f64{
-        1.
-    }
-}
-impl Bounded<f64,2> for Cylinders{
-    fn min(&self) -> f64 {
-        -1.
-    }
-    fn max(&self)->f64{
-        1.
-    }
-}
-
-impl Bounded<f64,2> for Checkerboard{
-    fn min(&self) -> f64 {
-        -1.
-    }
-
-    fn max(&self) -> f64 {
-        1.
-    }
-}
-impl<F> Bounded<f64,2> for Abs<f64, F, 2> where F: Bounded<f64,2>{
-    fn min(&self) -> f64 {
-
-
-        //! First scenario: both min and max are negative
-        //! Second scenario: min is positive, that implies that max is positive
-        //! Therefore the minima will be the minimum between the two absolute values
-        if (self.source.min() <0. && self.source.max() <0.) || (self.source.min() >0.){
-         return self.source.min().abs().min(self.source.max().abs())
-        }
-        else {
-            return 0.
-        }
-    }
-
-    fn max(&self) -> f64 {
-        self.source.min().abs().max(self.source.max().abs())
-    }
-}
-
-impl<F,F1> Bounded<f64,2> for Add<f64, F, F1, 2> where F: Bounded<f64,2>,F1: Bounded<f64,2>{
-    fn min(&self) -> f64 {
-        self.source1.min()+self.source2.min()
-    }
-
-    fn max(&self) -> f64 {
-        self.source1.max()+self.source2.max()
-    }
-}
-impl <F,F1> Bounded<f64,2> for Multiply<f64, F, F1, 2> where F: Bounded<f64,2>,F1: Bounded<f64,2>{
-
-    fn min(&self) -> f64 {
-        let v:[f64;4] = [self.source1.min()*self.source2.min(),self.source1.min()*self.source2.max(), self.source1.max()*self.source2.min(),self.source1.max()*self.source2.max()];
-        v.into_iter().min_by(|a,b | {if a < b {return Ordering::Less;} else {return Ordering::Greater}}).unwrap()
-
-    }
-
-    fn max(&self) -> f64 {
-        let v:[f64;4] = [self.source1.min()*self.source2.min(),self.source1.min()*self.source2.max(), self.source1.max()*self.source2.min(),self.source1.max()*self.source2.max()];
-        v.into_iter().max_by(|a,b | {if a < b {return Ordering::Less;} else {return Ordering::Greater}}).unwrap()
-    }
-}
-
-impl <F> Bounded<f64,2> for Clamp<f64,F,2> where F: Bounded<f64,2>{
-    fn min(&self) -> f64 {
-        self.bounds.0.max(self.source.min())
-    }
-
-    fn max(&self) -> f64 {
-        self.bounds.1.min(self.source.min())
-    }
-}
-
-
+use crate::worldgen::bounded::{Bounded, EmpiricalBounds};
+use crate::worldgen::noise_bundle::NoiseBundle;
 
 
 fn bound_accuracy_print<F>(function: F, test_cases: usize) where F: Bounded<f64,2>{
     let (mut min, mut max) = (0.,0.);
     let mut  rng = thread_rng();
-    for i in 0..test_cases{
+    for _ in 0..test_cases{
         let val =function.get([rng.gen(),rng.gen()]);
         min = val.min(min);
         max = val.max(max);
@@ -134,9 +24,82 @@ fn bound_accuracy_test() {
     let mut rng = thread_rng();
     //first let's try with a sum of perlin
     let f = Simplex::new(rng.gen());
-    let f1 = Perlin::new(rng.gen());
+    let _f1 = Perlin::new(rng.gen());
     //let sum = Add::new(f,f1);
     bound_accuracy_print(f,1000000);
     //bound_accuracy_print(f1,1000000);
 
 }
+
+#[test]
+fn combinators_stay_in_bounds() {
+    let mut rng = thread_rng();
+    let graph = Clamp::new(Multiply::new(Add::new(Perlin::new(1), Simplex::new(2)), Abs::new(Perlin::new(3))))
+        .set_bounds(-0.5, 0.8);
+    assert_eq!((graph.min(), graph.max()), (-0.5, 0.8));
+    for _ in 0..10000 {
+        let value = graph.get([rng.gen::<f64>() * 100., rng.gen::<f64>() * 100.]);
+        assert!(graph.min() <= value && value <= graph.max());
+    }
+
+    // Any bounded graph is remapped onto [0.0, 1.0]
+    let noise_bundle = NoiseBundle::new().set_noise_fn(Box::new(graph)).set_octaves(3);
+    for _ in 0..10000 {
+        let elevation = noise_bundle.sample_tile(rng.gen::<f64>() * 1000., rng.gen::<f64>() * 1000.);
+        assert!((0.0..=1.0).contains(&elevation));
+    }
+}
+
+fn assert_in_bounds<F>(function: &F) where F: Bounded<f64, 2> {
+    let mut rng = thread_rng();
+    let (min, max) = function.bounds();
+    assert!(min.is_finite() && max.is_finite() && min <= max);
+    for _ in 0..20000 {
+        let value = function.get([rng.gen::<f64>() * 100., rng.gen::<f64>() * 100.]);
+        assert!(min - 1e-9 <= value && value <= max + 1e-9, "{} out of [{}, {}]", value, min, max);
+    }
+}
+
+#[test]
+fn fractals_and_selectors_stay_in_bounds() {
+    assert_in_bounds(&Fbm::<Perlin>::new(1));
+    assert_in_bounds(&Billow::<Simplex>::new(2));
+    assert_in_bounds(&RidgedMulti::<Perlin>::new(3));
+    assert_in_bounds(&ScaleBias::new(Perlin::new(4)).set_scale(-3.).set_bias(0.5));
+    assert_in_bounds(&Exponent::new(Fbm::<Perlin>::new(5)).set_exponent(2.5));
+    assert_in_bounds(&Turbulence::<_, Perlin>::new(Billow::<Perlin>::new(6)).set_power(4.));
+    assert_in_bounds(&Select::new(Perlin::new(7), Constant::new(3.), Simplex::new(8)).set_bounds(0., 1.).set_falloff(0.2));
+    assert_in_bounds(&Blend::new(Perlin::new(9), Constant::new(2.), Perlin::new(10)));
+    assert_in_bounds(&Min::new(Perlin::new(11), Constant::new(0.2)));
+    assert_in_bounds(&Max::new(Perlin::new(12), Constant::new(0.2)));
+    assert_in_bounds(&Power::new(Abs::new(Perlin::new(13)), Constant::new(0.5)));
+    assert_in_bounds(&Power::new(Perlin::new(14), Constant::new(3.)));
+
+    // A fractal of [-1, 1] octaves is wider than [-1, 1]
+    let fbm = Fbm::<Perlin>::new(15);
+    assert!(fbm.max() > 1.);
+
+    // A negative base with a varying exponent has no analytic bounds
+    let power = Power::new(Perlin::new(16), Perlin::new(17));
+    assert_eq!(power.bounds(), (f64::NEG_INFINITY, f64::INFINITY));
+}
+
+#[test]
+fn empirical_bounds_are_reproducible() {
+    let worley = || EmpiricalBounds::new(Worley::new(18)).set_samples(20000).set_margin(0.1).set_extent(100.);
+    let bounds = worley().bounds();
+    assert_eq!(bounds, worley().bounds());
+    assert!(bounds.0.is_finite() && bounds.1.is_finite() && bounds.0 < bounds.1);
+
+    let function = worley();
+    let mut rng = thread_rng();
+    let outside = (0..20000)
+        .map(|_| function.get([rng.gen::<f64>() * 100., rng.gen::<f64>() * 100.]))
+        .filter(|value| !(bounds.0..=bounds.1).contains(value))
+        .count();
+    assert!(outside < 20);
+
+    // A bigger margin widens the bounds
+    let wider = worley().set_margin(0.5).bounds();
+    assert!(wider.0 < bounds.0 && wider.1 > bounds.1);
+}
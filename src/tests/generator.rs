@@ -1,23 +1,42 @@
 use std::collections::{HashMap, HashSet};
-use std::ptr::hash;
 use rand::Rng;
 use robotics_lib::world::tile::{Content, Tile, TileType};
 use robotics_lib::world::world_generator::Generator;
 
-use crate::{export::export_to_file::export_to_file, worldgen::{generator::WorldGenerator, noise_bundle::NoiseBundle}};
-use crate::worldgen::noise_bundle::ContentDist;
+use crate::worldgen::{generator::WorldGenerator, noise_bundle::NoiseBundle};
+use crate::worldgen::noise_bundle::{ContentDist, NoiseDistribution};
+use crate::worldgen::spawn::SpawnStrategy;
+use crate::worldgen::hydrology::RiverConfig;
+use crate::worldgen::erosion::{erode, ErosionConfig};
+use crate::worldgen::biome::{Biome, BiomeTable};
+use crate::worldgen::clustering::{cluster_contents, ContentClustering};
+use crate::worldgen::quota::ContentQuota;
+use crate::worldgen::teleport::{link_islands, TeleportConfig};
+use crate::worldgen::cliff::{raise_cliffs, CliffConfig};
+use crate::worldgen::settlement::{build_settlements, SettlementConfig};
+use crate::worldgen::road::{decorate_road, grade_roads, RoadConfig};
+use crate::worldgen::coverage::CoverageTargets;
+use crate::worldgen::warp::DomainWarp;
+use crate::utils::vector2::Vector2;
+use crate::utils::pathfinding::{build_road, Connectivity, Heuristic, PathError, PathFinder};
+use crate::utils::graph::{delaunay, minimum_spanning_tree};
+use crate::utils::generator_error::GeneratorError;
+use rand::{rngs::StdRng, SeedableRng};
 
-use super::*;
+/// width x height map of Grass without contents, flat at elevation 0
+fn flat_map(width: usize, height: usize) -> Vec<Vec<Tile>> {
+    vec![vec![Tile { tile_type: TileType::Grass, content: Content::None, elevation: 0 }; height]; width]
+}
 
 #[test]
-fn set_seed() -> (){
+fn set_seed(){
     let noise_bundle = NoiseBundle::new()
     .set_seed(42);
     assert_eq!(noise_bundle.get_seed(), 42);
 }
 
 #[test]
-fn set_octaves() -> (){
+fn set_octaves(){
     let noise_bundle = NoiseBundle::new()
     .set_seed(42)
     .set_octaves(7);
@@ -25,7 +44,7 @@ fn set_octaves() -> (){
 }
  
 #[test]
-fn not_jagged() -> () {
+fn not_jagged() {
     let size = 420;
     let mut generator = WorldGenerator::new().set_size(size);
     let map = generator.gen().0;
@@ -75,7 +94,7 @@ fn content_dist_is_respected(){
     let mut  actual_dist: HashMap<TileType,HashSet<Content>> = HashMap::new();
 
     for i in map.iter().flatten(){
-        let (tile_type, content) = (i.tile_type.clone(),i.content.clone());
+        let (tile_type, content) = (i.tile_type,i.content.clone());
         actual_dist.entry(tile_type).and_modify(|x| {x.insert(content.to_default());}).or_insert(HashSet::from([content.to_default()]));
     }
     let default_content_dist = ContentDist::default();
@@ -86,4 +105,594 @@ fn content_dist_is_respected(){
         assert!(content_set.is_subset(&distribution_content_set));
     }
 
-}
\ No newline at end of file
+}
+#[test]
+fn same_seed_same_world(){
+    let first = WorldGenerator::new().set_size(150).set_seed(42069).gen();
+    let second = WorldGenerator::new().set_size(150).set_seed(42069).gen();
+    assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&second).unwrap());
+}
+
+#[test]
+fn spawn_is_spawnable(){
+    let strategies = [SpawnStrategy::Random, SpawnStrategy::Center, SpawnStrategy::Road, SpawnStrategy::LargestLandZone];
+    for strategy in strategies {
+        let (map, (x, y), ..) = WorldGenerator::new()
+            .set_size(150)
+            .set_seed(420)
+            .set_spawn_strategy(strategy)
+            .gen();
+        assert!(![TileType::Lava, TileType::DeepWater, TileType::Wall].contains(&map[x][y].tile_type));
+        for row in &map[x.saturating_sub(2)..(x + 3).min(map.len())] {
+            for tile in &row[y.saturating_sub(2)..(y + 3).min(row.len())] {
+                assert_ne!(tile.tile_type, TileType::Lava);
+                assert_ne!(tile.content, Content::Fire);
+            }
+        }
+    }
+}
+
+#[test]
+fn rectangular_world(){
+    let (width, height) = (240, 90);
+    let mut generator = WorldGenerator::new().set_width(width).set_height(height);
+    let (map, (x, y), ..) = generator.gen();
+    assert_eq!(map.len(), width);
+    assert!(map.iter().all(|column| column.len() == height));
+    assert!(x < width && y < height);
+}
+
+#[test]
+fn threads_dont_change_the_world(){
+    let single = WorldGenerator::new().set_size(150).set_seed(69).set_threads(1).gen();
+    let many = WorldGenerator::new().set_size(150).set_seed(69).set_threads(8).gen();
+    assert_eq!(single.0, many.0);
+    assert_eq!(single.1, many.1);
+}
+
+#[test]
+fn chunks_stitch_seamlessly(){
+    let generator = WorldGenerator::new().set_seed(4242);
+    let whole = generator.gen_chunk(-50, 30, 160, 100);
+    for (chunk_x, chunk_y) in [(-50, 30), (30, 30), (-50, 80), (30, 80)] {
+        let chunk = generator.gen_chunk(chunk_x, chunk_y, 80, 50);
+        for x in 0..80 {
+            for y in 0..50 {
+                assert_eq!(chunk[x][y], whole[(chunk_x + 50) as usize + x][(chunk_y - 30) as usize + y]);
+            }
+        }
+    }
+}
+
+#[test]
+fn rivers_are_carved(){
+    let generate = |rivers: Option<RiverConfig>| {
+        WorldGenerator::new().set_size(200).set_seed(1234).set_rivers(rivers).gen().0
+    };
+    let water = |map: &Vec<Vec<Tile>>| map.iter().flatten().filter(|t| t.tile_type == TileType::ShallowWater).count();
+    let dry = generate(None);
+    let wet = generate(Some(RiverConfig::new().set_source_density(0.05).set_min_length(5)));
+    assert!(water(&wet) > water(&dry));
+}
+
+#[test]
+fn thermal_erosion_lowers_a_spike(){
+    // No rain, only the thermal erosion: 1/8 of the excess over the talus angle slides to every 4-neighbour
+    let mut heightmap = vec![vec![0.0; 5]; 5];
+    heightmap[2][2] = 1.0;
+    let config = ErosionConfig::new().set_iterations(1).set_rain_amount(0.0).set_talus_angle(0.1).set_thermal_rate(0.5);
+    erode(&mut heightmap, &config);
+
+    let slide = 0.5 * (1.0 - 0.1) / 8.0;
+    assert!((heightmap[2][2] - (1.0 - 4.0 * slide)).abs() < 1e-12);
+    for (x, y) in [(1, 2), (3, 2), (2, 1), (2, 3)] {
+        assert!((heightmap[x][y] - slide).abs() < 1e-12);
+    }
+    assert_eq!(heightmap[1][1], 0.0);
+    assert!((heightmap.iter().flatten().sum::<f64>() - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn erosion_changes_the_terrain(){
+    let generate = |erosion: Option<ErosionConfig>| {
+        WorldGenerator::new().set_size(120).set_seed(777).set_elevation_factor(100.0).set_erosion(erosion).gen().0
+    };
+    assert_ne!(generate(None), generate(Some(ErosionConfig::new().set_iterations(20))));
+}
+
+#[test]
+fn biomes_decide_the_tiletype(){
+    let biomes = BiomeTable::new(vec![Biome::new("Prairie", TileType::Grass)]);
+    let noise_bundle = NoiseBundle::new().set_seed(99).set_biomes(Some(biomes)).unwrap();
+    let map = WorldGenerator::new().set_size(150).set_noise_bundle(noise_bundle).gen().0;
+    assert!(map.iter().flatten().all(|t| t.tile_type == TileType::Grass || t.tile_type == TileType::Street));
+}
+
+#[test]
+fn invalid_biome_content_is_rejected(){
+    assert_eq!(BiomeTable::default().is_valid(), Ok(()));
+    let biomes = BiomeTable::new(vec![Biome::new("Swamp", TileType::Grass).set_content(vec![(0..50, Content::None)])]);
+    assert!(NoiseBundle::new().set_biomes(Some(biomes)).is_err());
+    assert_eq!(BiomeTable::new(vec![Biome::new("Maze", TileType::Wall)]).is_valid(), Err(GeneratorError::WallInNoiseDistribution));
+    assert_eq!(BiomeTable::new(vec![Biome::new("Portal", TileType::Teleport(true))]).is_valid(), Err(GeneratorError::ActiveTeleport));
+}
+
+#[test]
+fn clustering_keeps_the_frequencies(){
+    let mut rng = StdRng::seed_from_u64(31337);
+    let content_dist = ContentDist::default();
+    let mut map: Vec<Vec<Tile>> = (0..120).map(|_| (0..120).map(|_| Tile {
+        tile_type: TileType::Grass,
+        content: content_dist.get_content(TileType::Grass, rng.gen_range(0..=100)),
+        elevation: 0,
+    }).collect()).collect();
+
+    let count = |map: &Vec<Vec<Tile>>| {
+        let mut counter: HashMap<Content, usize> = HashMap::new();
+        for tile in map.iter().flatten() {
+            *counter.entry(tile.content.clone()).or_default() += 1;
+        }
+        counter
+    };
+    // Trees with a Tree on the right
+    let neighbouring_trees = |map: &Vec<Vec<Tile>>| (0..119).flat_map(|x| (0..120).map(move |y| (x, y)))
+        .filter(|(x, y)| matches!(map[*x][*y].content, Content::Tree(_)) && matches!(map[x + 1][*y].content, Content::Tree(_)))
+        .count();
+
+    let (before, scattered) = (count(&map), neighbouring_trees(&map));
+    cluster_contents(&mut map, &ContentClustering::new(), 31337);
+    assert_eq!(before, count(&map));
+    assert!(neighbouring_trees(&map) > 2 * scattered);
+}
+
+#[test]
+fn quotas_are_met(){
+    let map = WorldGenerator::new()
+        .set_size(150)
+        .set_seed(3)
+        .set_content_quota(Content::Bank(0..10), ContentQuota::Exactly(3))
+        .set_content_quota(Content::Coin(1), ContentQuota::AtLeast(2000))
+        .set_content_quota(Content::Market(1), ContentQuota::Between(1, 2))
+        .gen().0;
+    let count = |kind: Content| map.iter().flatten().filter(|t| t.content.to_default() == kind.to_default()).count();
+    assert_eq!(count(Content::Bank(0..0)), 3);
+    assert!(count(Content::Coin(0)) >= 2000);
+    assert!((1..=2).contains(&count(Content::Market(0))));
+}
+
+#[test]
+fn quotas_keep_the_fire_away_from_the_spawn(){
+    let world = WorldGenerator::new()
+        .set_size(60)
+        .set_seed(8)
+        .set_spawn_safe_radius(4)
+        .set_content_quota(Content::Fire, ContentQuota::AtLeast(1500))
+        .gen();
+    let spawn = world.1;
+    let fires = world.0.iter().enumerate()
+        .flat_map(|(x, column)| column.iter().enumerate().map(move |(y, tile)| (x, y, tile)))
+        .filter(|(_, _, tile)| tile.content == Content::Fire)
+        .collect::<Vec<_>>();
+    assert!(fires.len() >= 1500);
+    assert!(fires.iter().all(|(x, y, _)| x.abs_diff(spawn.0) > 4 || y.abs_diff(spawn.1) > 4));
+}
+
+#[test]
+fn unsatisfiable_quota_fails(){
+    let result = WorldGenerator::new()
+        .set_size(50)
+        .set_content_quota(Content::Building, ContentQuota::AtLeast(50 * 50 + 1))
+        .try_gen();
+    assert_eq!(result.err(), Some(GeneratorError::UnsatisfiableQuota(Content::Building)));
+}
+
+#[test]
+fn contents_above_the_maximum_fail(){
+    // A quota with a quantity robotics_lib can't hold is rejected before the generation
+    let result = WorldGenerator::new().set_size(20).set_content_quota(Content::Coin(1000), ContentQuota::AtLeast(1)).try_gen();
+    assert_eq!(result.err(), Some(GeneratorError::MaxContent(Content::Coin(1000))));
+
+    // The contents of the ContentDist are checked on the generated map, by default
+    let mut content_distribution = ContentDist::default();
+    content_distribution.dist.insert(TileType::Grass, vec![(0..101, Content::Rock(1000))]);
+    let noise_bundle = NoiseBundle::new().set_seed(5).set_content_distribution(content_distribution).unwrap();
+    let result = WorldGenerator::new().set_size(100).set_noise_bundle(noise_bundle).try_gen();
+    assert_eq!(result.err(), Some(GeneratorError::MaxContent(Content::Rock(1000))));
+}
+
+#[test]
+fn try_gen_validates_the_config(){
+    assert_eq!(WorldGenerator::new().set_size(0).try_gen().err(), Some(GeneratorError::InvalidWorldSize));
+    assert_eq!(WorldGenerator::new().set_width(10).set_height(0).try_gen().err(), Some(GeneratorError::InvalidWorldSize));
+    assert_eq!(WorldGenerator::new().set_size(10).set_weather_forecast(5, 10, 0).try_gen().err(), Some(GeneratorError::InvalidWeather));
+    assert_eq!(WorldGenerator::new().set_size(10).set_weather_forecast(24, 10, 5).try_gen().err(), Some(GeneratorError::InvalidWeather));
+    assert_eq!(WorldGenerator::new().set_size(10).set_max_score(f32::NAN).try_gen().err(), Some(GeneratorError::InvalidScoreTable));
+    assert_eq!(
+        WorldGenerator::new().set_size(10).set_content_quota(Content::Coin(1), ContentQuota::Between(5, 2)).try_gen().err(),
+        Some(GeneratorError::InvalidQuota(Content::Coin(1)))
+    );
+    let everything = vec![TileType::DeepWater, TileType::ShallowWater, TileType::Sand, TileType::Grass, TileType::Hill,
+                          TileType::Mountain, TileType::Snow, TileType::Lava];
+    assert_eq!(WorldGenerator::new().set_size(10).set_notspawnable(everything).try_gen().err(), Some(GeneratorError::InvalidNotSpawnable));
+    assert!(WorldGenerator::new().set_size(60).try_gen().is_ok());
+
+    let parameter = |name: &str| Some(GeneratorError::InvalidParameter(name.to_string()));
+    assert_eq!(WorldGenerator::new().set_size(10).set_erosion(Some(ErosionConfig::new().set_rain_amount(-0.1))).try_gen().err(),
+               parameter("erosion.rain_amount"));
+    assert_eq!(WorldGenerator::new().set_size(10).set_erosion(Some(ErosionConfig::new().set_talus_angle(f64::NAN))).try_gen().err(),
+               parameter("erosion.talus_angle"));
+    assert_eq!(WorldGenerator::new().set_size(10).set_rivers(Some(RiverConfig::new().set_max_width(0))).try_gen().err(),
+               parameter("rivers.max_width"));
+    assert_eq!(WorldGenerator::new().set_size(10).set_teleports(Some(TeleportConfig::new().set_min_island_size(0))).try_gen().err(),
+               parameter("teleports.min_island_size"));
+    let clustering = NoiseBundle::new().set_content_clustering(Some(ContentClustering::new().set_period(0.0)));
+    assert_eq!(WorldGenerator::new().set_size(10).set_noise_bundle(clustering).try_gen().err(),
+               parameter("content_clustering.period"));
+    assert_eq!(WorldGenerator::new().set_size(10).set_settlements(Some(SettlementConfig::new().set_block(1))).try_gen().err(),
+               parameter("settlements.block"));
+    assert_eq!(WorldGenerator::new().set_size(10).set_road_loops(1.5).try_gen().err(), parameter("road_loops"));
+    assert_eq!(WorldGenerator::new().set_size(10).set_roads(RoadConfig::new().add_decoration(Content::Bin(0..5), 0)).try_gen().err(),
+               parameter("roads.decorations"));
+    let coverage = |coverage: CoverageTargets| WorldGenerator::new().set_size(10).set_coverage(Some(coverage)).try_gen().err();
+    assert_eq!(coverage(CoverageTargets::new()), parameter("coverage"));
+    assert_eq!(coverage(CoverageTargets::new().add_group(vec![TileType::Grass], 0.7)), parameter("coverage"));
+    assert_eq!(coverage(CoverageTargets::new().add_group(vec![TileType::Grass], 0.5).add_group(vec![TileType::Grass], 0.5)), parameter("coverage"));
+    assert_eq!(NoiseBundle::new().set_domain_warp(Some(DomainWarp::new().add_layer(1.0, 0.0))).err(), parameter("domain_warp"));
+    assert_eq!(NoiseBundle::new().set_domain_warp(Some(DomainWarp::new().add_layer(f64::NAN, 1.0))).err(), parameter("domain_warp"));
+}
+
+#[test]
+#[should_panic(expected = "InvalidWorldSize")]
+fn gen_validates_the_config(){
+    WorldGenerator::new().set_size(0).gen();
+}
+
+#[test]
+fn default_noise_distribution_is_valid(){
+    assert_eq!(NoiseDistribution::default().is_valid(), Ok(()));
+}
+
+#[test]
+fn invalid_noise_distribution_is_rejected(){
+    let build = |bands: Vec<(std::ops::Range<usize>, Vec<TileType>)>| {
+        bands.into_iter().fold(NoiseDistribution::builder(), |builder, (range, types)| builder.band(range, types)).build()
+    };
+    assert_eq!(build(vec![]).err(), Some(GeneratorError::EmptyNoiseDistribution));
+    assert_eq!(build(vec![(0..50, vec![TileType::Grass]), (60..101, vec![TileType::Hill])]).err(), Some(GeneratorError::NoiseDistributionGap));
+    assert_eq!(build(vec![(0..50, vec![TileType::Grass])]).err(), Some(GeneratorError::NoiseDistributionGap));
+    assert_eq!(build(vec![(0..60, vec![TileType::Grass]), (50..101, vec![TileType::Hill])]).err(), Some(GeneratorError::OverlappingDistribution));
+    assert_eq!(build(vec![(0..50, vec![]), (50..101, vec![TileType::Hill])]).err(), Some(GeneratorError::EmptyNoiseBand));
+    assert_eq!(build(vec![(0..101, vec![TileType::Wall])]).err(), Some(GeneratorError::WallInNoiseDistribution));
+    assert_eq!(build(vec![(0..101, vec![TileType::Teleport(true)])]).err(), Some(GeneratorError::ActiveTeleport));
+
+    let islands = build(vec![(0..40, vec![TileType::DeepWater]), (40..101, vec![TileType::Grass])]).unwrap();
+    let world = WorldGenerator::new().set_size(40)
+        .set_noise_bundle(NoiseBundle::new().set_noise_distribution(islands).unwrap())
+        .try_gen().unwrap();
+    assert!(world.0.iter().flatten().all(|tile| matches!(tile.tile_type, TileType::DeepWater | TileType::Grass | TileType::Street)));
+}
+
+#[test]
+fn islands_are_linked_by_teleports(){
+    let water = Tile { tile_type: TileType::DeepWater, content: Content::None, elevation: 0 };
+    let mut map = vec![vec![water; 40]; 40];
+    for (x0, y0, side) in [(2, 2, 10), (25, 25, 8), (30, 2, 2)] {
+        for column in &mut map[x0..x0 + side] {
+            for tile in &mut column[y0..y0 + side] {
+                *tile = Tile { tile_type: TileType::Grass, content: Content::Rock(1), elevation: 0 };
+            }
+        }
+    }
+    // A Lava bridge doesn't join the islands, the robot can't walk on it
+    for column in &mut map[12..25] {
+        column[10].tile_type = TileType::Lava;
+    }
+    for tile in &mut map[24][10..25] {
+        tile.tile_type = TileType::Lava;
+    }
+    let config = TeleportConfig::new().set_min_island_size(16);
+    let pairs = link_islands(&mut map, Vector2::new(5, 5), &config, &mut StdRng::seed_from_u64(1));
+
+    // The 2x2 island is too small
+    assert_eq!(pairs.len(), 1);
+    let (landing, island) = pairs[0];
+    assert!(landing.x < 12 && landing.y < 12);
+    assert!(island.x >= 25 && island.y >= 25);
+    for p in [landing, island] {
+        assert_eq!(map[p.x][p.y].tile_type, TileType::Teleport(false));
+        assert_eq!(map[p.x][p.y].content, Content::None);
+    }
+
+    // Sand islands in a deep sea: the roads only reach the Grass zones, the Sand ones need a teleport
+    let islands = NoiseDistribution::builder()
+        .band(0..45, vec![TileType::DeepWater])
+        .band(45..101, vec![TileType::Grass, TileType::Sand])
+        .build()
+        .unwrap();
+    let noise_bundle = NoiseBundle::new().set_seed(4).set_scale(20.0).set_noise_distribution(islands).unwrap();
+    let mut generator = WorldGenerator::new()
+        .set_size(200)
+        .set_noise_bundle(noise_bundle)
+        .set_notspawnable(vec![TileType::DeepWater, TileType::Lava, TileType::Sand])
+        .set_teleports(Some(TeleportConfig::new()));
+    let world = generator.gen();
+    assert!(!generator.get_report().get_teleports().is_empty());
+    for (landing, island) in generator.get_report().get_teleports() {
+        assert_eq!(world.0[landing.0][landing.1].tile_type, TileType::Teleport(false));
+        assert_eq!(world.0[island.0][island.1].tile_type, TileType::Teleport(false));
+    }
+}
+
+#[test]
+fn cliffs_have_gaps(){
+    let mut map = flat_map(30, 60);
+    let heightmap: Vec<Vec<f64>> = (0..30).map(|x| vec![if x < 15 { 0.2 } else { 0.8 }; 60]).collect();
+    let config = CliffConfig::new().set_max_length(10).set_gap_frequency(0.0).set_gap_width(2);
+    raise_cliffs(&mut map, &heightmap, &config, &mut StdRng::seed_from_u64(1));
+
+    // A single line on the top edge: 10 walls and 2 gaps, 5 times
+    let walls: Vec<Vector2> = (0..30).flat_map(|x| (0..60).map(move |y| Vector2::new(x, y)))
+        .filter(|p| map[p.x][p.y].tile_type == TileType::Wall)
+        .collect();
+    assert_eq!(walls.len(), 50);
+    assert!(walls.iter().all(|p| p.x == 15));
+
+    // The roads go through the gaps
+    let path = PathFinder::new().find(&map, (0, 0), (29, 0)).unwrap();
+    assert!(path.tiles.iter().all(|(x, y)| map[*x][*y].tile_type != TileType::Wall));
+
+    // A cliff three tiles thick: every gap is a passage through the whole slope
+    let mut map = flat_map(30, 60);
+    let heightmap: Vec<Vec<f64>> = (0..30).map(|x| vec![[0.2, 0.4, 0.6, 0.8][x.clamp(9, 12) - 9]; 60]).collect();
+    let config = CliffConfig::new().set_max_length(10).set_gap_frequency(0.0).set_gap_width(2);
+    raise_cliffs(&mut map, &heightmap, &config, &mut StdRng::seed_from_u64(1));
+    assert!(map[11].iter().any(|tile| tile.tile_type == TileType::Wall));
+    assert!(PathFinder::new().find(&map, (0, 0), (29, 59)).is_ok());
+
+    let world = WorldGenerator::new().set_size(200).set_seed(5).set_cliffs(Some(CliffConfig::new().set_threshold(0.005))).gen();
+    assert!(world.0.iter().flatten().any(|tile| tile.tile_type == TileType::Wall));
+    assert_ne!(world.0[world.1.0][world.1.1].tile_type, TileType::Wall);
+}
+
+#[test]
+fn settlements_have_markets(){
+    let config = SettlementConfig::new().set_count(3).set_size(5);
+    let mut generator = WorldGenerator::new().set_size(300).set_seed(11).set_settlements(Some(config));
+    let map = generator.gen().0;
+    let settlements = generator.get_report().get_settlements().to_vec();
+    assert!(!settlements.is_empty() && settlements.len() <= 3);
+    for (x, y) in settlements {
+        let map = &map;
+        let square = || (x - 5..=x + 5).flat_map(move |i| (y - 5..=y + 5).map(move |j| &map[i][j]));
+        assert!(square().any(|tile| matches!(tile.content, Content::Market(_))));
+        assert!(square().filter(|tile| tile.tile_type == TileType::Street).count() > 20);
+    }
+}
+
+#[test]
+fn road_graph_is_a_spanning_tree(){
+    let square = [Vector2::new(0, 0), Vector2::new(10, 0), Vector2::new(0, 10), Vector2::new(10, 10)];
+    let edges = delaunay(&square);
+    assert_eq!(edges.len(), 5);
+    let (tree, others) = minimum_spanning_tree(&square, &edges);
+    assert_eq!((tree.len(), others.len()), (3, 2));
+
+    // Collinear and duplicated points are still connected
+    let line = [Vector2::new(0, 5), Vector2::new(4, 5), Vector2::new(9, 5), Vector2::new(4, 5)];
+    let (tree, _) = minimum_spanning_tree(&line, &delaunay(&line));
+    assert_eq!(tree.len(), 3);
+
+    let mut rng = StdRng::seed_from_u64(4);
+    let points: Vec<Vector2> = (0..200).map(|_| Vector2::new(rng.gen_range(0..500), rng.gen_range(0..500))).collect();
+    let (tree, _) = minimum_spanning_tree(&points, &delaunay(&points));
+    assert_eq!(tree.len(), points.len() - 1);
+
+    let streets = |loops: f64| WorldGenerator::new().set_size(200).set_seed(8).set_road_loops(loops).gen().0
+        .iter().flatten().filter(|tile| tile.tile_type == TileType::Street).count();
+    assert!(streets(1.0) >= streets(0.0));
+}
+
+#[test]
+fn path_finder_is_reusable(){
+    let mut map = flat_map(20, 20);
+    for tile in &mut map[10][..19] {
+        tile.tile_type = TileType::Wall;
+    }
+    let mut finder = PathFinder::new();
+    for _ in 0..3 {
+        // Around the end of the wall: 19 steps down, 10 right and 19 up
+        let path = finder.find(&map, (5, 0), (15, 0)).unwrap();
+        assert_eq!(path.tiles.len(), 49);
+        assert_eq!((path.tiles[0], path.tiles[48]), ((5, 0), (15, 0)));
+        assert_eq!(path.cost, 480);
+    }
+    assert_eq!(finder.find(&map[..5], (0, 0), (4, 19)).map(|path| path.tiles.len()), Ok(24));
+    assert_eq!(finder.find(&map, (5, 0), (10, 0)), Err(PathError::NotWalkable((10, 0))));
+    assert_eq!(finder.find(&map, (5, 0), (20, 0)), Err(PathError::OutOfBounds((20, 0))));
+
+    let mut eight = PathFinder::new().set_connectivity(Connectivity::Eight).set_heuristic(Heuristic::Octile);
+    let path = eight.find(&map, (0, 0), (9, 9)).unwrap();
+    assert_eq!((path.tiles.len(), path.cost), (10, 9 * 14));
+    // The corners of the wall can't be cut, 4 straight steps around its end
+    assert_eq!(eight.find(&map, (9, 18), (11, 18)).map(|path| path.tiles.len()), Ok(5));
+
+    let mut cheap = PathFinder::new().set_max_cost(Some(300));
+    assert_eq!(cheap.find(&map, (5, 0), (15, 0)), Err(PathError::MaxCostExceeded));
+    map[10][19].tile_type = TileType::Wall;
+    assert_eq!(finder.find(&map, (5, 0), (15, 0)), Err(PathError::Unreachable));
+    // The cutoff pruned some cells, but the wall is what cuts the end off
+    let mut cheaper = PathFinder::new().set_max_cost(Some(100));
+    assert_eq!(cheaper.find(&map, (5, 0), (15, 0)), Err(PathError::Unreachable));
+}
+
+#[test]
+fn exact_roads_take_the_cheapest_way(){
+    // A Street detour far from the straight line: free to walk, but Manhattan doesn't know it
+    let mut map = flat_map(40, 40);
+    for column in map.iter_mut() {
+        column[39].tile_type = TileType::Street;
+    }
+    for x in [0, 39] {
+        for tile in map[x].iter_mut() {
+            tile.tile_type = TileType::Street;
+        }
+    }
+    let fast = RoadConfig::new().finder().find(&map, (1, 0), (38, 0)).unwrap();
+    let exact = RoadConfig::new().set_exact(true).finder().find(&map, (1, 0), (38, 0)).unwrap();
+    // Around the three sides of the map, only the last step is on Grass
+    assert_eq!(exact.cost, 10);
+    assert!(fast.cost > exact.cost, "{}", fast.cost);
+}
+
+#[test]
+fn roads_respect_the_slopes(){
+    // A ridge in the middle of the map, with a pass at y = 0
+    let mut map = flat_map(21, 30);
+    for tile in &mut map[10][1..] {
+        tile.elevation = 20;
+    }
+    let flat = PathFinder::new().find(&map, (0, 15), (20, 15)).unwrap();
+    assert!(flat.tiles.contains(&(10, 15)));
+    let steep = PathFinder::new().set_slope_penalty(5).find(&map, (0, 15), (20, 15)).unwrap();
+    assert!(steep.tiles.contains(&(10, 0)));
+
+    // A bumpy road is graded to steps of at most 2
+    let mut road: Vec<Vec<Tile>> = (0..40).map(|x| vec![Tile { tile_type: TileType::Street, content: Content::None, elevation: (x % 4) * 10 }]).collect();
+    grade_roads(&mut road, 2);
+    assert!(road.windows(2).all(|w| w[0][0].elevation.abs_diff(w[1][0].elevation) <= 2));
+
+    // The streets of the settlements are graded too, even on a rugged terrain
+    let map = WorldGenerator::new().set_size(150).set_seed(6).set_elevation_factor(5000.0)
+        .set_roads(RoadConfig::new().set_slope_penalty(1).set_switchbacks(true).set_max_step(Some(3)))
+        .set_settlements(Some(SettlementConfig::new().set_count(3).set_size(5)))
+        .gen().0;
+    for (x, column) in map.iter().enumerate() {
+        for (y, tile) in column.iter().enumerate() {
+            for next in [map.get(x + 1).map(|column| &column[y]), column.get(y + 1)].into_iter().flatten() {
+                if tile.tile_type == TileType::Street && next.tile_type == TileType::Street {
+                    assert!(tile.elevation.abs_diff(next.elevation) <= 3);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn roads_get_street_contents(){
+    let mut map = flat_map(40, 1);
+    for tile in map.iter_mut().flatten() {
+        tile.content = Content::Tree(1);
+    }
+    let path: Vec<Vector2> = (0..40).map(|x| Vector2::new(x, 0)).collect();
+    let mut rng = StdRng::seed_from_u64(3);
+    let noise_bundle = NoiseBundle::new();
+    build_road(&mut map, &path, |_| noise_bundle.put_content(TileType::Street, &mut rng));
+    assert!(map.iter().flatten().all(|tile| tile.tile_type == TileType::Street && tile.content != Content::Tree(1)));
+
+    let roads = RoadConfig::new().add_decoration(Content::Bin(0..5), 10);
+    decorate_road(&mut map, &path, &roads);
+    let bins: Vec<usize> = (0..40).filter(|x| map[*x][0].content == Content::Bin(0..5)).collect();
+    assert_eq!(bins, vec![9, 19, 29, 39]);
+
+    // A settlement on a crossroads keeps the contents of the roads
+    let mut map = flat_map(21, 21);
+    for (x, column) in map.iter_mut().enumerate() {
+        for (y, tile) in column.iter_mut().enumerate() {
+            if x == 10 || y == 10 {
+                *tile = Tile { tile_type: TileType::Street, content: Content::Bin(0..5), elevation: 0 };
+            }
+        }
+    }
+    let config = SettlementConfig::new().set_count(1).set_size(5).set_block(4);
+    let centers = build_settlements(&mut map, &config, &noise_bundle, &mut StdRng::seed_from_u64(2));
+    assert_eq!(centers, vec![Vector2::new(10, 10)]);
+    assert!(map.iter().flatten().filter(|tile| tile.tile_type == TileType::Street).count() > 41);
+    assert!(map[10].iter().chain(map.iter().map(|column| &column[10])).all(|tile| tile.content == Content::Bin(0..5)));
+
+    let map = WorldGenerator::new().set_size(300).set_seed(9).gen().0;
+    let streets: Vec<&Tile> = map.iter().flatten().filter(|tile| tile.tile_type == TileType::Street).collect();
+    assert!(streets.iter().any(|tile| tile.content != Content::None));
+}
+
+#[test]
+fn equalized_bands_cover_their_share(){
+    let distribution = || NoiseDistribution::builder()
+        .band(0..25, vec![TileType::DeepWater])
+        .band(25..101, vec![TileType::Grass])
+        .build().unwrap();
+    let share = |map: &Vec<Vec<Tile>>| {
+        map.iter().flatten().filter(|tile| tile.tile_type == TileType::DeepWater).count() as f64 / map.iter().flatten().count() as f64
+    };
+
+    let bundle = NoiseBundle::new().set_seed(23).set_noise_distribution(distribution()).unwrap().set_equalize(true);
+    let world = WorldGenerator::new().set_size(128).set_noise_bundle(bundle).try_gen().unwrap();
+    // Only the roads and the spawn area may cover some water
+    assert!((share(&world.0) - 0.25).abs() < 0.02, "{}", share(&world.0));
+
+    // The chunks use the quantiles of the noise field
+    let bundle = NoiseBundle::new().set_seed(23).set_period(64.0).set_noise_distribution(distribution()).unwrap().set_equalize(true);
+    let generator = WorldGenerator::new().set_noise_bundle(bundle);
+    let chunk = generator.gen_chunk(0, 0, 256, 256);
+    assert!((share(&chunk) - 0.25).abs() < 0.05, "{}", share(&chunk));
+    let whole = generator.gen_chunk(-20, -20, 100, 100);
+    let part = generator.gen_chunk(30, 30, 50, 50);
+    for x in 0..50 {
+        for y in 0..50 {
+            assert_eq!(part[x][y], whole[x + 50][y + 50]);
+        }
+    }
+}
+
+#[test]
+fn coverage_targets_are_met(){
+    let coverage = CoverageTargets::new()
+        .add_group(vec![TileType::DeepWater, TileType::ShallowWater], 0.4)
+        .add_group(vec![TileType::Grass, TileType::Sand], 0.5)
+        .add_group(vec![TileType::Mountain], 0.1);
+    let mut generator = WorldGenerator::new().set_size(128)
+        .set_noise_bundle(NoiseBundle::new().set_seed(24))
+        .set_coverage(Some(coverage));
+    let world = generator.try_gen().unwrap();
+
+    let report = generator.get_report().get_coverage();
+    assert_eq!(report.len(), 3);
+    for (group, requested) in report.iter().zip([0.4, 0.5, 0.1]) {
+        assert_eq!(group.requested, requested);
+        // The roads and the spawn area replace a few tiles
+        assert!((group.actual - requested).abs() < 0.02, "{:?}", group);
+        let count = world.0.iter().flatten().filter(|tile| group.tile_types.contains(&tile.tile_type)).count();
+        assert_eq!(group.actual, count as f64 / (128.0 * 128.0));
+    }
+
+    let walls = CoverageTargets::new().add_group(vec![TileType::Wall], 1.0);
+    assert_eq!(WorldGenerator::new().set_size(16).set_coverage(Some(walls)).try_gen().err(), Some(GeneratorError::WallInNoiseDistribution));
+}
+
+#[test]
+fn domain_warp_moves_the_terrain(){
+    let warp = || DomainWarp::new().add_layer(0.8, 0.5).add_layer(0.2, 2.0);
+    let grid = |bundle: &NoiseBundle| (0..64).flat_map(|x| (0..64).map(move |y| (x, y)))
+        .map(|(x, y)| bundle.sample_tile(x as f64 * 16.0, y as f64 * 16.0))
+        .collect::<Vec<f64>>();
+
+    let plain = NoiseBundle::new().set_seed(25);
+    let warped = NoiseBundle::new().set_seed(25).set_domain_warp(Some(warp())).unwrap();
+    assert!(grid(&plain) != grid(&warped));
+    // The warp only moves the sampled points, the elevation stays in [0.0, 1.0]
+    assert!(grid(&warped).iter().all(|elevation| (0.0..=1.0).contains(elevation)));
+
+    // The warp noises follow the master seed, whatever the order of the setters
+    let reseeded = NoiseBundle::new().set_domain_warp(Some(warp())).unwrap().set_seed(25);
+    assert!(grid(&warped) == grid(&reseeded));
+
+    let world = WorldGenerator::new().set_size(64).set_noise_bundle(warped).try_gen();
+    assert!(world.is_ok());
+}
+
+#[test]
+fn weather_forecast_starts_at_the_hour(){
+    let world = WorldGenerator::new().set_size(10).set_weather_forecast(12, 30, 25).try_gen().unwrap();
+    assert_eq!(world.2.get_time_of_day(), (12, 0));
+    let world = WorldGenerator::new().set_size(10).try_gen().unwrap();
+    assert_eq!(world.2.get_time_of_day(), (5, 0));
+}
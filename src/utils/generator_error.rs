@@ -3,8 +3,18 @@ use robotics_lib::world::tile::{Content, TileType};
 #[derive(Debug,Eq,PartialEq)]
 pub enum GeneratorError{
     InvalidWorldSize,
+    EmptyNoiseDistribution,
+    EmptyNoiseBand,
+    NoiseDistributionGap,
+    WallInNoiseDistribution,
+    InvalidNotSpawnable,
+    InvalidScoreTable,
+    InvalidWeather,
+    InvalidQuota(Content),
+    InvalidParameter(String),
     InvalidContent(TileType,Content),
     MaxContent(Content),
+    UnsatisfiableQuota(Content),
     NonExhaustiveContentDistribution,
     OverlappingDistribution,
     ActiveTeleport,
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use super::vector2::Vector2;
+
+/// Triangle of the triangulation, indices of its vertices and its circumcircle
+#[derive(Debug, Clone, Copy)]
+struct Triangle {
+    vertices: [usize; 3],
+    center: (f64, f64),
+    radius_squared: f64,
+}
+
+impl Triangle {
+    fn new(vertices: [usize; 3], points: &[(f64, f64)]) -> Self {
+        let [(ax, ay), (bx, by), (cx, cy)] = vertices.map(|v| points[v]);
+        let d = 2.0 * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+        let (a, b, c) = (ax * ax + ay * ay, bx * bx + by * by, cx * cx + cy * cy);
+        let center = (
+            (a * (by - cy) + b * (cy - ay) + c * (ay - by)) / d,
+            (a * (cx - bx) + b * (ax - cx) + c * (bx - ax)) / d,
+        );
+        let radius_squared = (ax - center.0).powi(2) + (ay - center.1).powi(2);
+        Self { vertices, center, radius_squared }
+    }
+
+    fn circumcircle_contains(&self, (x, y): (f64, f64)) -> bool {
+        (x - self.center.0).powi(2) + (y - self.center.1).powi(2) < self.radius_squared
+    }
+
+    fn edges(&self) -> [(usize, usize); 3] {
+        let [a, b, c] = self.vertices;
+        [(a.min(b), a.max(b)), (b.min(c), b.max(c)), (a.min(c), a.max(c))]
+    }
+}
+
+/// Edges of the Delaunay triangulation of the points (Bowyer-Watson), as pairs of indices with i < j.
+/// Duplicated points are joined by an edge to their first copy.
+/// The edges of the triangles touching the super triangle are kept too, so collinear points
+/// are still connected in a chain.
+pub(crate) fn delaunay(points: &[Vector2]) -> Vec<(usize, usize)> {
+    // First copy of every distinct point
+    let mut first = HashMap::<Vector2, usize>::new();
+    let mut edges = Vec::<(usize, usize)>::new();
+    let mut unique = Vec::<usize>::new();
+    for (i, p) in points.iter().enumerate() {
+        match first.get(p) {
+            Some(j) => edges.push((*j, i)),
+            None => {
+                first.insert(*p, i);
+                unique.push(i);
+            }
+        }
+    }
+    if unique.len() < 2 {
+        return edges;
+    }
+
+    // Coordinates of the unique points, then the 3 vertices of the super triangle
+    let mut coords: Vec<(f64, f64)> = unique.iter().map(|i| (points[*i].x as f64, points[*i].y as f64)).collect();
+    let (min_x, max_x) = coords.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+    let (min_y, max_y) = coords.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+    let span = (max_x - min_x).max(max_y - min_y).max(1.0) * 20.0;
+    let (mid_x, mid_y) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+    let n = coords.len();
+    coords.extend([(mid_x - span, mid_y - span), (mid_x + span, mid_y - span), (mid_x, mid_y + span)]);
+
+    let mut triangles = vec![Triangle::new([n, n + 1, n + 2], &coords)];
+    for v in 0..n {
+        // The triangles whose circumcircle contains the point leave a polygonal hole,
+        // its boundary is made of the edges belonging to only one of them
+        let (bad, good): (Vec<Triangle>, Vec<Triangle>) = triangles.into_iter().partition(|t| t.circumcircle_contains(coords[v]));
+        let mut count = HashMap::<(usize, usize), usize>::new();
+        for edge in bad.iter().flat_map(|t| t.edges()) {
+            *count.entry(edge).or_default() += 1;
+        }
+        triangles = good;
+        for t in bad.iter() {
+            for (a, b) in t.edges() {
+                if count[&(a, b)] == 1 {
+                    triangles.push(Triangle::new([a, b, v], &coords));
+                }
+            }
+        }
+    }
+
+    let mut unique_edges: Vec<(usize, usize)> = triangles.iter()
+        .flat_map(|t| t.edges())
+        .filter(|(a, b)| *a < n && *b < n)
+        .map(|(a, b)| (unique[a].min(unique[b]), unique[a].max(unique[b])))
+        .collect();
+    unique_edges.sort_unstable();
+    unique_edges.dedup();
+    edges.extend(unique_edges);
+    edges
+}
+
+/// Union find with path halving
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    /// false if i and j were already joined
+    fn union(&mut self, i: usize, j: usize) -> bool {
+        let (i, j) = (self.find(i), self.find(j));
+        self.parent[i] = j;
+        i != j
+    }
+}
+
+/// Edges as pairs of indices in the points
+type Edges = Vec<(usize, usize)>;
+
+/// Kruskal on the edges weighted by the Manhattan distance of their points.
+/// Returns the edges of the minimum spanning tree (a forest if the edges don't connect
+/// every point) and the other edges, both sorted by length.
+pub(crate) fn minimum_spanning_tree(points: &[Vector2], edges: &[(usize, usize)]) -> (Edges, Edges) {
+    let mut sorted = edges.to_vec();
+    sorted.sort_by_key(|(a, b)| (points[*a].manhattan_distance(points[*b]), *a, *b));
+
+    let mut set = DisjointSet::new(points.len());
+    sorted.into_iter().partition(|(a, b)| set.union(*a, *b))
+}
@@ -1,5 +1,7 @@
 pub mod zone;
 pub(crate) mod tile;
 pub (crate) mod vector2;
-pub (crate) mod pathfinding;
+pub mod pathfinding;
 pub mod generator_error;
+
+pub (crate) mod graph;
\ No newline at end of file
@@ -1,209 +1,322 @@
-use std::cmp::{Ordering, Reverse};
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 
-use priority_queue::PriorityQueue;
-use rand::prelude::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
 use robotics_lib::world::tile::{Content, Tile, TileType};
 use crate::utils::vector2::Vector2;
-use crate::worldgen::noise_bundle::ContentDist;
 
-pub(crate) trait Walkable {
+/// A grid cell the PathFinder can search on
+pub trait Walkable {
+    /// Cost of entering the cell
     fn cost(&self) -> u32;
+    /// Impassable cells are never part of a path
+    fn is_walkable(&self) -> bool {
+        true
+    }
+    /// Used by the slope penalty of the PathFinder
+    fn elevation(&self) -> usize {
+        0
+    }
 }
 
-/// Struct representing a node of the map for pathfinding purposes
-#[derive(Debug)]
-struct Node{
-    //the coordinates of the point on the map
-    coords: Vector2,
-    //the node's parent's coordinates in the shortest path
-    parent:Option<Vector2>,
-    //g value: represents the shortest distance from the start point
-    g: u32,
-    //h value:(optimistic) estimated distance from the end point
-    h:u32,
-    //weight: the weight of the node
-    weight:u32
+/// Why PathFinder::find didn't return a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    EmptyMap,
+    OutOfBounds((usize, usize)),
+    /// The end is not walkable
+    NotWalkable((usize, usize)),
+    /// The impassable cells cut the end off
+    Unreachable,
+    /// Every path to the end costs more than the max_cost
+    MaxCostExceeded,
 }
-impl Node {
-    fn new(coords: Vector2, g:u32, h:u32, weight:u32) ->Self{
 
-        Self {
-            coords,
-            parent:None,
-            g,
-            h,
-            weight
+/// Moves allowed from a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    /// Left, right, up, down
+    #[default]
+    Four,
+    /// Also the diagonals, a diagonal move can't cut the corner of an impassable cell
+    Eight,
+}
+
+/// Estimate of the cost to the end, multiplied by the heuristic_scale of the PathFinder.
+/// The path is the shortest one if the estimate never exceeds the real cost:
+/// with a scale equal to the lowest Walkable::cost of the map, Manhattan is exact for
+/// Connectivity::Four and Octile for Connectivity::Eight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Heuristic {
+    /// Dijkstra
+    Zero,
+    #[default]
+    Manhattan,
+    Chebyshev,
+    Octile,
+}
+
+impl Heuristic {
+    /// In the unit of the costs of the PathFinder, 10 for a straight step and 14 for a diagonal one
+    fn estimate(&self, from: Vector2, to: Vector2) -> u32 {
+        let (dx, dy) = (from.x.abs_diff(to.x) as u32, from.y.abs_diff(to.y) as u32);
+        match self {
+            Heuristic::Zero => 0,
+            Heuristic::Manhattan => 10 * (dx + dy),
+            Heuristic::Chebyshev => 10 * dx.max(dy),
+            Heuristic::Octile => 10 * dx.max(dy) + 4 * dx.min(dy),
         }
     }
-    ///function used to initialize a node to a default value
-    fn init(coords: Vector2, weight:u32, end: Vector2) -> Self{
+}
+
+/// Path found by PathFinder::find
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    /// From the start to the end, both included
+    pub tiles: Vec<(usize, usize)>,
+    /// In tenths of Walkable::cost: entering a cell costs 10 * (cost + slope) with a straight step
+    /// and 14 * (cost + slope) with a diagonal one, the start is free
+    pub cost: u32,
+}
+
+/// A* over a grid of Walkable cells, map[x][y].
+/// The buffers are flat (index = x * height + y), allocated once and invalidated by bumping
+/// a generation stamp instead of clearing them: many searches on the same map with
+/// one PathFinder allocate nothing after the first one.
+/// The search is unidirectional over the full grid, there is no bidirectional or hierarchical mode.
+pub struct PathFinder {
+    connectivity: Connectivity,
+    heuristic: Heuristic,
+    heuristic_scale: u32,
+    max_cost: Option<u32>,
+    slope_penalty: u32,
+    slope_exponent: u32,
+    width: usize,
+    height: usize,
+    /// Best known g cost of every cell, valid if seen[i] == generation
+    g: Vec<u32>,
+    /// Index of the parent of every cell in the best known path
+    parent: Vec<u32>,
+    seen: Vec<u32>,
+    /// Cell already expanded if closed[i] == generation
+    closed: Vec<u32>,
+    generation: u32,
+    /// (f cost, tie break, index), stale entries are skipped when popped
+    heap: BinaryHeap<Reverse<(u32, u32, u32)>>,
+}
+
+impl Default for PathFinder {
+    fn default() -> Self {
         Self {
-            coords,
-            parent:None,
-            g: 1000000,
-            h: coords.manhattan_distance(end),
-            weight:weight*10
+            connectivity: Connectivity::default(),
+            heuristic: Heuristic::default(),
+            heuristic_scale: 1,
+            max_cost: None,
+            slope_penalty: 0,
+            slope_exponent: 1,
+            width: 0,
+            height: 0,
+            g: vec![],
+            parent: vec![],
+            seen: vec![],
+            closed: vec![],
+            generation: 0,
+            heap: BinaryHeap::new(),
         }
     }
+}
 
-    fn f_cost(&self) -> u32{
-        self.g_cost()+self.h
+impl PathFinder {
+    pub fn new() -> Self {
+        Self::default()
     }
-    ///function that connects the Node to a parent,
-    fn connect(&mut self, parent: Vector2, parent_cost: u32,){
-        self.parent = Some(parent);
-        self.g = parent_cost;
-        /*
-        let (c_tuple, e_tuple) = (self.coords.as_i32_tuple(),end.as_i32_tuple());
-        let h = (c_tuple.0 - e_tuple.0).abs()+(c_tuple.1 - e_tuple.1).abs();
-        */
 
+    pub fn set_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
 
+    pub fn set_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
     }
 
-    fn g_cost(&self) -> u32{
-        self.g +self.weight
+    /// Multiplier of the heuristic, above the lowest Walkable::cost the search is faster
+    /// but the path may not be the shortest one
+    pub fn set_heuristic_scale(mut self, heuristic_scale: u32) -> Self {
+        self.heuristic_scale = heuristic_scale;
+        self
     }
 
-}
+    /// The search gives up on the paths costing more than max_cost (same unit of Path::cost)
+    pub fn set_max_cost(mut self, max_cost: Option<u32>) -> Self {
+        self.max_cost = max_cost;
+        self
+    }
 
+    /// Extra cost of a step between two cells whose elevation differs by d:
+    /// slope_penalty * d^slope_exponent. 0 ignores the elevation
+    pub fn set_slope_penalty(mut self, slope_penalty: u32) -> Self {
+        self.slope_penalty = slope_penalty;
+        self
+    }
 
-impl PartialEq<Self> for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.f_cost() == other.f_cost()
+    /// With an exponent above 1 a steep step costs more than many gentle ones,
+    /// the paths climb the slopes in switchbacks
+    pub fn set_slope_exponent(mut self, slope_exponent: u32) -> Self {
+        self.slope_exponent = slope_exponent;
+        self
     }
-}
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.f_cost().partial_cmp(&other.f_cost())
+    /// Cheapest path from start to end, ties are broken in favour of the cells closer to the end
+    pub fn find<T: Walkable>(&mut self, map: &[Vec<T>], start: (usize, usize), end: (usize, usize)) -> Result<Path, PathError> {
+        let start = Vector2::new(start.0, start.1);
+        let end = Vector2::new(end.0, end.1);
+        let (tiles, cost) = self.search(map, start, end, || 0)?;
+        Ok(Path { tiles: tiles.into_iter().map(|p| p.as_tuple()).collect(), cost })
     }
-}
 
+    /// Same as find, the rng breaks the ties: the generation uses its own rng to stay reproducible
+    /// and to avoid roads that always turn the same way
+    pub(crate) fn find_with_rng<T: Walkable, R: Rng>(&mut self, map: &[Vec<T>], start: Vector2, end: Vector2, rng: &mut R) -> Result<Vec<Vector2>, PathError> {
+        self.search(map, start, end, || rng.gen()).map(|(tiles, _)| tiles)
+    }
 
-pub(crate) fn shortest_path<T>(map: &Vec<Vec<T>>, start: Vector2, end: Vector2) -> Result<Vec<Vector2>, String>
-    where
-        T: Walkable,
-{
-    //check if path gets out of bounds
-    if map.is_empty()|| map[0].is_empty() {
-        return Err("Invalid Map".to_string())
-    }
-    let (width,height) = (map.len(),map[0].len());
-    if !( start.x < width &&  start.y < height){
-        return Err("Out of bounds".to_string());
-    }
-    //initializes the node map with the coordinates and default values
-    let mut node_map: Vec<Vec<Node>> = map.iter().enumerate().map(|(col,v)|
-        v.iter().enumerate().map(|(row,w)| Node::init(Vector2::new(col, row), w.cost(), end)  ).collect()).collect();
-    node_map[start.x][start.y] = Node::new(start,0,0,0);
-
-    //the frontier of the pathfinding algorithm
-    let mut open_list = OpenList::new();
-
-    //nodes already verified
-    let mut closed_list = HashSet::<Vector2>::new();
-    //add start to open list
-    open_list.add(start,&node_map);
-
-    loop{
-        //gets point with smallest f_cost
-        let current = open_list.min();
-        closed_list.insert(current);
-        if current == end{
-            break;
+    /// Get the buffers ready for a search on a width x height map
+    fn reset(&mut self, width: usize, height: usize) {
+        if (width, height) != (self.width, self.height) || self.generation == u32::MAX {
+            let size = width * height;
+            (self.width, self.height) = (width, height);
+            self.g = vec![0; size];
+            self.parent = vec![0; size];
+            self.seen = vec![0; size];
+            self.closed = vec![0; size];
+            self.generation = 0;
         }
+        self.generation += 1;
+        self.heap.clear();
+    }
 
-        for i in neighbours(current,width,height).iter(){
-            if closed_list.contains(i)&& node_map[i.x][i.y].g_cost()<= node_map[start.x][start.y].g_cost()+ node_map[i.x][i.y].weight{
-                continue;
-            }
-            if !open_list.contains(i) || node_map[i.x][i.y].g_cost() >node_map[start.x][start.y].g_cost() + node_map[i.x][i.y].weight {
-                let parent_cost = node_map[start.x][start.y].g_cost();
-
-                node_map[i.x][i.y].connect(current,parent_cost);
-
-                open_list.add(*i,&node_map);
-
+    /// Moves from p as (neighbour, step cost multiplier), the corners of impassable cells aren't cut
+    fn moves<T: Walkable>(&self, map: &[Vec<T>], p: Vector2) -> [Option<(Vector2, u32)>; 8] {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let walkable = |x: i64, y: i64| x >= 0 && y >= 0 && x < width && y < height && map[x as usize][y as usize].is_walkable();
+        let diagonals = self.connectivity == Connectivity::Eight;
+        let (px, py) = (p.x as i64, p.y as i64);
+        [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)].map(|(dx, dy)| {
+            let diagonal = dx != 0 && dy != 0;
+            let allowed = walkable(px + dx, py + dy) && (!diagonal || (diagonals && walkable(px + dx, py) && walkable(px, py + dy)));
+            allowed.then(|| (Vector2::new((px + dx) as usize, (py + dy) as usize), if diagonal { 14 } else { 10 }))
+        })
+    }
 
+    /// A*, returns the path from start to end and its cost
+    fn search<T: Walkable, F: FnMut() -> u32>(&mut self, map: &[Vec<T>], start: Vector2, end: Vector2, mut tie_break: F) -> Result<(Vec<Vector2>, u32), PathError> {
+        if map.is_empty() || map[0].is_empty() {
+            return Err(PathError::EmptyMap);
+        }
+        let (width, height) = (map.len(), map[0].len());
+        for p in [start, end] {
+            if !(p.x < width && p.y < height) {
+                return Err(PathError::OutOfBounds(p.as_tuple()));
             }
-
-
         }
-    }
-    let mut path: Vec<Vector2> = Vec::new();
-    let mut current = end;
-    while current != start {
-        path.push(current);
-        if let Some(p) = node_map[current.x][current.y].parent{
-            current = p;
-        }else {
-            return Err("Invalid Path :(".to_string());
+        if !map[end.x][end.y].is_walkable() {
+            return Err(PathError::NotWalkable(end.as_tuple()));
         }
-    }
-    //println!("cost sum : {}", path.iter().fold(0,|x,p| x+node_map[p.x][p.y].f_cost()));
 
-    Ok(path)
-
-}
+        self.reset(width, height);
+        let generation = self.generation;
+        let index = |p: Vector2| (p.x * height + p.y) as u32;
+        let point = |i: u32| Vector2::new(i as usize / height, i as usize % height);
+        let (heuristic, scale) = (self.heuristic, self.heuristic_scale);
+        let estimate = |p: Vector2| heuristic.estimate(p, end).saturating_mul(scale);
+
+        let (start_index, end_index) = (index(start), index(end));
+        self.g[start_index as usize] = 0;
+        self.seen[start_index as usize] = generation;
+        self.heap.push(Reverse((estimate(start), tie_break(), start_index)));
+        let mut pruned = false;
+
+        while let Some(Reverse((_, _, current))) = self.heap.pop() {
+            if self.closed[current as usize] == generation {
+                continue;
+            }
+            self.closed[current as usize] = generation;
+            if current == end_index {
+                let mut path = vec![end];
+                let mut i = end_index;
+                while i != start_index {
+                    i = self.parent[i as usize];
+                    path.push(point(i));
+                }
+                path.reverse();
+                return Ok((path, self.g[end_index as usize]));
+            }
 
-pub fn build_road(world:&mut Vec<Vec<Tile>>, path: Vec<Vector2>){
-    for i in path {
-        world[i.x][i.y].tile_type = TileType::Street;
-        world[i.x][i.y].content = Content::None;
-    
+            let g = self.g[current as usize];
+            let p = point(current);
+            for (n, step) in self.moves(map, p).into_iter().flatten() {
+                let i = index(n);
+                if self.closed[i as usize] == generation {
+                    continue;
+                }
+                let slope = (map[n.x][n.y].elevation().abs_diff(map[p.x][p.y].elevation()) as u32)
+                    .saturating_pow(self.slope_exponent)
+                    .saturating_mul(self.slope_penalty);
+                let cost = g.saturating_add(map[n.x][n.y].cost().saturating_add(slope).saturating_mul(step));
+                if self.max_cost.is_some_and(|max_cost| cost > max_cost) {
+                    pruned = true;
+                    continue;
+                }
+                if self.seen[i as usize] != generation || cost < self.g[i as usize] {
+                    self.seen[i as usize] = generation;
+                    self.g[i as usize] = cost;
+                    self.parent[i as usize] = current;
+                    // Equal f: the cell closer to the end first, then the tie break
+                    let h = estimate(n);
+                    self.heap.push(Reverse((cost.saturating_add(h), h.min(u16::MAX as u32) << 16 | (tie_break() & 0xFFFF), i)));
+                }
+            }
+        }
+        // The cutoff only explains the failure if the end can be reached at any cost
+        if pruned && self.reachable(map, start, end) {
+            Err(PathError::MaxCostExceeded)
+        } else {
+            Err(PathError::Unreachable)
+        }
     }
 
-}
-
-fn neighbours(coords: Vector2, width:usize, height:usize) -> Vec<Vector2> {
-    let mut vec: Vec<Vector2> = Vec::with_capacity(4);
-    //⇐
-    if coords.x > 0{
-        vec.push(Vector2::new(coords.x-1, coords.y))
-    }
-    //⇒
-    if coords.x < width-1{
-        vec.push(Vector2::new(coords.x+1, coords.y))
-    }
-    //⇑
-    if coords.y > 0{
-        vec.push(Vector2::new(coords.x, coords.y-1))
-    }
-    //⇓
-    if coords.y < height-1{
-        vec.push(Vector2::new(coords.x, coords.y+1))
+    /// Flood fill from start over the walkable cells, ignoring the costs and the max_cost
+    fn reachable<T: Walkable>(&mut self, map: &[Vec<T>], start: Vector2, end: Vector2) -> bool {
+        self.reset(self.width, self.height);
+        let (generation, height) = (self.generation, self.height);
+        let index = |p: Vector2| p.x * height + p.y;
+        let mut stack = vec![start];
+        self.seen[index(start)] = generation;
+        while let Some(p) = stack.pop() {
+            if p == end {
+                return true;
+            }
+            for (n, _) in self.moves(map, p).into_iter().flatten() {
+                if self.seen[index(n)] != generation {
+                    self.seen[index(n)] = generation;
+                    stack.push(n);
+                }
+            }
+        }
+        false
     }
-    vec.shuffle(&mut thread_rng());
-    vec
 }
 
-
-///wrapper for priority queue
-pub struct OpenList {
-    pq: PriorityQueue<Vector2,Reverse<u32>>
-}
-impl OpenList{
-    fn new() -> Self{
-        Self {
-            pq: PriorityQueue::new()
-        }
-    }
-    fn min(&mut self) -> Vector2 {
-        self.pq.pop().map(|(a,_)| a).unwrap()
-    }
-    fn add(&mut self, item: Vector2, map: &Vec<Vec<Node>>){
-        if self.contains(&item){
-            self.pq.change_priority(&item,Reverse(map[item.x][item.y].f_cost()));
-        }else{
-            self.pq.push(item, Reverse(map[item.x][item.y].f_cost()));
+/// Pave the path, the tiles that weren't a Street yet get their content from content
+pub(crate) fn build_road<F: FnMut(Vector2) -> Content>(world: &mut [Vec<Tile>], path: &[Vector2], mut content: F){
+    for i in path {
+        if world[i.x][i.y].tile_type != TileType::Street {
+            world[i.x][i.y].tile_type = TileType::Street;
+            world[i.x][i.y].content = content(*i);
         }
     }
-    fn contains(&self, item: &Vector2) -> bool{
-    self.pq.get(item).map_or_else(|| false, |_| true)
-    }
 }
 
 impl Walkable for Tile {
@@ -222,4 +335,12 @@ impl Walkable for Tile {
             TileType::Wall=> 0,
         }
     }
+
+    fn is_walkable(&self) -> bool {
+        self.tile_type != TileType::Wall
+    }
+
+    fn elevation(&self) -> usize {
+        self.elevation
+    }
 }
@@ -0,0 +1,202 @@
+use std::ops::Range;
+
+use noise::{NoiseFn, Perlin};
+use rand::Rng;
+use robotics_lib::world::tile::{Content, TileType};
+
+use crate::utils::generator_error::GeneratorError;
+
+use super::noise_bundle::ContentDist;
+
+/// A cell of the biome table: the TileType (and optionally the contents) of the tiles
+/// whose elevation, moisture and temperature, all in [0, 100], fall in its ranges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Biome {
+    name: String,
+    elevation: Range<usize>,
+    moisture: Range<usize>,
+    temperature: Range<usize>,
+    tile_type: TileType,
+    /// Same format of a ContentDist entry, if None the ContentDist of the NoiseBundle is used
+    content: Option<Vec<(Range<usize>, Content)>>,
+}
+
+impl Biome {
+    /// Biome covering every elevation, moisture and temperature
+    pub fn new(name: &str, tile_type: TileType) -> Self {
+        Self {
+            name: name.to_string(),
+            elevation: 0..101,
+            moisture: 0..101,
+            temperature: 0..101,
+            tile_type,
+            content: None,
+        }
+    }
+
+    pub fn set_elevation(mut self, elevation: Range<usize>) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    pub fn set_moisture(mut self, moisture: Range<usize>) -> Self {
+        self.moisture = moisture;
+        self
+    }
+
+    pub fn set_temperature(mut self, temperature: Range<usize>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn set_content(mut self, content: Vec<(Range<usize>, Content)>) -> Self {
+        self.content = Some(content);
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_tile_type(&self) -> TileType {
+        self.tile_type
+    }
+
+    fn contains(&self, elevation: usize, moisture: usize, temperature: usize) -> bool {
+        self.elevation.contains(&elevation) && self.moisture.contains(&moisture) && self.temperature.contains(&temperature)
+    }
+
+    /// Content from the biome profile, None if the biome doesn't have one
+    pub(crate) fn put_content<R: Rng>(&self, rng: &mut R) -> Option<Content> {
+        let content = self.content.as_ref()?;
+        let value = rng.gen_range(0..=100);
+        Some(content.iter()
+            .find(|(range, _)| range.contains(&value))
+            .map_or(Content::None, |(_, content)| content.clone()))
+    }
+}
+
+/// Whittaker-style table: elevation x moisture x temperature -> Biome.
+/// Moisture and temperature are two extra noise layers seeded from the NoiseBundle seed,
+/// the temperature can be mixed with a latitude gradient (cold poles, hot equator).
+/// The first biome matching a tile wins, tiles not matched by any biome keep the
+/// TileType of the noise_distribution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeTable {
+    biomes: Vec<Biome>,
+    /// Tiles spanned by a unit of the moisture and temperature noises
+    layer_period: f64,
+    /// Weight of the latitude in the temperature, in [0.0, 1.0], 0 means noise only
+    latitude_gradient: f64,
+    /// Tiles from a pole to the next one along y, the equator is halfway.
+    /// Set it to the map height to have the poles on the top and bottom border.
+    latitude_period: f64,
+}
+
+impl Default for BiomeTable {
+    fn default() -> Self {
+        let forest = vec![
+            (0..40, Content::None),
+            (40..85, Content::Tree(2)),
+            (85..92, Content::Bush(1)),
+            (92..97, Content::Rock(1)),
+            (97..101, Content::Coin(2)),
+        ];
+        let desert = vec![
+            (0..88, Content::None),
+            (88..94, Content::Rock(1)),
+            (94..98, Content::Coin(3)),
+            (98..101, Content::Scarecrow),
+        ];
+
+        Self {
+            biomes: vec![
+                Biome::new("Ocean", TileType::DeepWater).set_elevation(0..25),
+                Biome::new("Coast", TileType::ShallowWater).set_elevation(25..35),
+                Biome::new("Tundra", TileType::Snow).set_elevation(35..60).set_temperature(0..20),
+                Biome::new("Desert", TileType::Sand).set_elevation(35..60).set_moisture(0..30).set_content(desert),
+                Biome::new("Forest", TileType::Grass).set_elevation(35..60).set_moisture(60..101).set_content(forest),
+                Biome::new("Grassland", TileType::Grass).set_elevation(35..60),
+                Biome::new("Glacier", TileType::Snow).set_elevation(60..101).set_temperature(0..30),
+                Biome::new("Volcano", TileType::Lava).set_elevation(85..101).set_moisture(0..25).set_temperature(70..101),
+                Biome::new("Highland", TileType::Hill).set_elevation(60..70),
+                Biome::new("Mountain", TileType::Mountain).set_elevation(70..85),
+                Biome::new("Peak", TileType::Snow).set_elevation(85..101).set_temperature(0..60),
+                Biome::new("Crag", TileType::Mountain).set_elevation(85..101),
+            ],
+            layer_period: 512.0,
+            latitude_gradient: 0.0,
+            latitude_period: 1024.0,
+        }
+    }
+}
+
+impl BiomeTable {
+    pub fn new(biomes: Vec<Biome>) -> Self {
+        Self { biomes, ..Self::default() }
+    }
+
+    pub fn set_layer_period(mut self, layer_period: f64) -> Self {
+        self.layer_period = layer_period;
+        self
+    }
+
+    pub fn set_latitude_gradient(mut self, latitude_gradient: f64) -> Self {
+        self.latitude_gradient = latitude_gradient;
+        self
+    }
+
+    pub fn set_latitude_period(mut self, latitude_period: f64) -> Self {
+        self.latitude_period = latitude_period;
+        self
+    }
+
+    /// Same rules of ContentDist::is_valid for the content profiles, no Wall and no active teleports
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        for biome in self.biomes.iter() {
+            if biome.tile_type == TileType::Wall {
+                return Err(GeneratorError::WallInNoiseDistribution);
+            }
+            if biome.tile_type == TileType::Teleport(true) {
+                return Err(GeneratorError::ActiveTeleport);
+            }
+            if let Some(content) = &biome.content {
+                ContentDist::is_valid_entry(&biome.tile_type, content)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The two noise layers of the table, seeded from the master seed
+    pub(crate) fn sampler(&self, seed: u32) -> BiomeSampler<'_> {
+        BiomeSampler {
+            table: self,
+            moisture: Perlin::new(seed.wrapping_add(2)),
+            temperature: Perlin::new(seed.wrapping_add(3)),
+        }
+    }
+}
+
+pub(crate) struct BiomeSampler<'a> {
+    table: &'a BiomeTable,
+    moisture: Perlin,
+    temperature: Perlin,
+}
+
+impl BiomeSampler<'_> {
+    /// Biome of the tile at the world coordinates (x, y), elevation in [0, 100]
+    pub(crate) fn biome(&self, x: f64, y: f64, elevation: usize) -> Option<&Biome> {
+        let point = [x / self.table.layer_period, y / self.table.layer_period];
+        let moisture = (self.moisture.get(point) + 1.0) / 2.0;
+        let mut temperature = (self.temperature.get(point) + 1.0) / 2.0;
+
+        if self.table.latitude_gradient > 0.0 {
+            // 0 at the poles, 1 at the equator
+            let latitude = 1.0 - ((y / self.table.latitude_period).rem_euclid(1.0) * 2.0 - 1.0).abs();
+            temperature = temperature * (1.0 - self.table.latitude_gradient) + latitude * self.table.latitude_gradient;
+        }
+
+        let to_percent = |value: f64| (value * 100.0).clamp(0.0, 100.0) as usize;
+        self.table.biomes.iter().find(|biome| biome.contains(elevation, to_percent(moisture), to_percent(temperature)))
+    }
+}
@@ -0,0 +1,451 @@
+use std::sync::OnceLock;
+
+use noise::{
+    Abs, Add, Billow, Blend, Checkerboard, Clamp, Constant, Cylinders, Exponent, Fbm, Max, Min, Multiply, NoiseFn, Perlin,
+    PerlinSurflet, Power, RidgedMulti, ScaleBias, Seedable, Select, Simplex, SuperSimplex, Turbulence,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// A noise function whose output is known to stay in [min(), max()].
+/// NoiseBundle uses the bounds to remap the elevation exactly onto [0.0, 1.0],
+/// whatever the range of the noise graph.
+pub trait Bounded<T, const DIM: usize>: NoiseFn<T, DIM> {
+    fn min(&self) -> f64;
+    fn max(&self) -> f64;
+
+    fn bounds(&self) -> (f64, f64) {
+        (self.min(), self.max())
+    }
+}
+
+/// Wraps a noise function with bounds known by the user, for the noise graphs
+/// whose bounds can't be derived from their parts
+pub struct WithBounds<F> {
+    pub source: F,
+    pub bounds: (f64, f64),
+}
+
+impl<F> WithBounds<F> {
+    pub fn new(source: F, min: f64, max: f64) -> Self {
+        Self { source, bounds: (min, max) }
+    }
+}
+
+impl<T, F, const DIM: usize> NoiseFn<T, DIM> for WithBounds<F> where F: NoiseFn<T, DIM> {
+    fn get(&self, point: [T; DIM]) -> f64 {
+        self.source.get(point)
+    }
+}
+
+impl<T, F, const DIM: usize> Bounded<T, DIM> for WithBounds<F> where F: NoiseFn<T, DIM> {
+    fn min(&self) -> f64 {
+        self.bounds.0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds.1
+    }
+}
+
+impl<T, M, const DIM: usize> Bounded<T, DIM> for Box<M> where M: Bounded<T, DIM> + ?Sized {
+    fn min(&self) -> f64 {
+        M::min(self)
+    }
+
+    fn max(&self) -> f64 {
+        M::max(self)
+    }
+}
+
+/// Bounds estimated by sampling the noise function, for the noise graphs whose bounds
+/// can't be derived analytically (like a Power with a negative base).
+/// The samples are taken once, on the first call to min() or max(), at random points of
+/// the square [-extent, extent]², then the range is widened by margin * (max - min) on both sides.
+/// The sampling is seeded: the same source always gets the same bounds.
+pub struct EmpiricalBounds<F> {
+    source: F,
+    samples: usize,
+    margin: f64,
+    extent: f64,
+    seed: u64,
+    bounds: OnceLock<(f64, f64)>,
+}
+
+impl<F> EmpiricalBounds<F> {
+    pub fn new(source: F) -> Self {
+        Self {
+            source,
+            samples: 10_000,
+            margin: 0.05,
+            extent: 256.,
+            seed: 0,
+            bounds: OnceLock::new(),
+        }
+    }
+
+    /// Number of points sampled, the more the tighter the bounds
+    pub fn set_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self.bounds = OnceLock::new();
+        self
+    }
+
+    /// Fraction of the sampled range added below the min and above the max,
+    /// the values the samples missed are out of the bounds without it
+    pub fn set_margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self.bounds = OnceLock::new();
+        self
+    }
+
+    /// Half side of the sampled square, in noise units
+    pub fn set_extent(mut self, extent: f64) -> Self {
+        self.extent = extent;
+        self.bounds = OnceLock::new();
+        self
+    }
+
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.bounds = OnceLock::new();
+        self
+    }
+
+    pub fn get_source(&self) -> &F {
+        &self.source
+    }
+}
+
+impl<F> EmpiricalBounds<F> where F: NoiseFn<f64, 2> {
+    /// The NaN samples are skipped, without any other sample the bounds are NaN
+    /// and the WorldGenerator rejects the noise function
+    fn estimate(&self) -> (f64, f64) {
+        *self.bounds.get_or_init(|| {
+            let mut rng = StdRng::seed_from_u64(self.seed);
+            let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+            for _ in 0..self.samples {
+                let point = [rng.gen_range(-1.0..=1.0) * self.extent, rng.gen_range(-1.0..=1.0) * self.extent];
+                let value = self.source.get(point);
+                if !value.is_nan() {
+                    min = min.min(value);
+                    max = max.max(value);
+                }
+            }
+            if min > max {
+                return (f64::NAN, f64::NAN);
+            }
+            let margin = (max - min) * self.margin;
+            (min - margin, max + margin)
+        })
+    }
+}
+
+impl<F> NoiseFn<f64, 2> for EmpiricalBounds<F> where F: NoiseFn<f64, 2> {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        self.source.get(point)
+    }
+}
+
+impl<F> Bounded<f64, 2> for EmpiricalBounds<F> where F: NoiseFn<f64, 2> {
+    fn min(&self) -> f64 {
+        self.estimate().0
+    }
+
+    fn max(&self) -> f64 {
+        self.estimate().1
+    }
+}
+
+/// The gradient noises and the patterns of the noise crate are all in [-1.0, 1.0]
+macro_rules! unit_bounded {
+    ($($noise:ty),*) => {
+        $(
+            impl Bounded<f64, 2> for $noise {
+                fn min(&self) -> f64 {
+                    -1.
+                }
+
+                fn max(&self) -> f64 {
+                    1.
+                }
+            }
+        )*
+    };
+}
+
+unit_bounded!(Perlin, PerlinSurflet, Simplex, SuperSimplex, Cylinders, Checkerboard);
+
+impl<F> Bounded<f64, 2> for Abs<f64, F, 2> where F: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        abs(self.source.bounds()).0
+    }
+
+    fn max(&self) -> f64 {
+        abs(self.source.bounds()).1
+    }
+}
+
+impl<F, F1> Bounded<f64, 2> for Add<f64, F, F1, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.source1.min() + self.source2.min()
+    }
+
+    fn max(&self) -> f64 {
+        self.source1.max() + self.source2.max()
+    }
+}
+
+/// The extremes of a product are among the products of the extremes
+fn products(a: (f64, f64), b: (f64, f64)) -> [f64; 4] {
+    [a.0 * b.0, a.0 * b.1, a.1 * b.0, a.1 * b.1]
+}
+
+impl<F, F1> Bounded<f64, 2> for Multiply<f64, F, F1, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        products((self.source1.min(), self.source1.max()), (self.source2.min(), self.source2.max()))
+            .into_iter()
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn max(&self) -> f64 {
+        products((self.source1.min(), self.source1.max()), (self.source2.min(), self.source2.max()))
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+}
+
+impl<F> Bounded<f64, 2> for Clamp<f64, F, 2> where F: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.bounds.0.max(self.source.min())
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds.1.min(self.source.max())
+    }
+}
+
+/// [a, b] * k
+fn scale(a: (f64, f64), k: f64) -> (f64, f64) {
+    let (x, y) = (a.0 * k, a.1 * k);
+    (x.min(y), x.max(y))
+}
+
+/// |[a, b]|: both bounds on the same side of 0, otherwise 0 is the min
+fn abs(a: (f64, f64)) -> (f64, f64) {
+    let max = a.0.abs().max(a.1.abs());
+    if a.0 >= 0. {
+        (a.0, max)
+    } else if a.1 <= 0. {
+        (-a.1, max)
+    } else {
+        (0., max)
+    }
+}
+
+/// Smallest range holding both
+fn hull(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0.min(b.0), a.1.max(b.1))
+}
+
+fn min_max(values: impl IntoIterator<Item = f64>) -> (f64, f64) {
+    values
+        .into_iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| (min.min(v), max.max(v)))
+}
+
+/// Sum over the octaves of signal(octave) * persistence^octave, divided by 1 - persistence^octaves.
+/// The scale factor is computed from the public fields, like the fractal does when they are set
+/// with the MultiFractal setters
+fn fractal(octaves: usize, persistence: f64, signal: impl Fn(usize) -> (f64, f64)) -> (f64, f64) {
+    let sum = (0..octaves)
+        .map(|x| scale(signal(x), persistence.powi(x as i32)))
+        .fold((0., 0.), |sum, s| (sum.0 + s.0, sum.1 + s.1));
+    scale(sum, 1. / (1. - persistence.powi(octaves as i32)))
+}
+
+impl Bounded<f64, 2> for Constant {
+    fn min(&self) -> f64 {
+        self.value
+    }
+
+    fn max(&self) -> f64 {
+        self.value
+    }
+}
+
+// The sources of the fractals are private: their bounds are the ones of a default T,
+// a fractal built with set_sources must use sources with the same bounds.
+
+impl<T> Bounded<f64, 2> for Fbm<T> where T: Bounded<f64, 2> + Default + Seedable {
+    fn min(&self) -> f64 {
+        self.bounds().0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds().1
+    }
+
+    fn bounds(&self) -> (f64, f64) {
+        let source = T::default().bounds();
+        fractal(self.octaves, self.persistence, |_| source)
+    }
+}
+
+impl<T> Bounded<f64, 2> for Billow<T> where T: Bounded<f64, 2> + Default + Seedable {
+    fn min(&self) -> f64 {
+        self.bounds().0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds().1
+    }
+
+    /// Every signal is |s| * 2 - 1
+    fn bounds(&self) -> (f64, f64) {
+        let signal = scale(abs(T::default().bounds()), 2.);
+        let signal = (signal.0 - 1., signal.1 - 1.);
+        fractal(self.octaves, self.persistence, |_| signal)
+    }
+}
+
+impl<T> Bounded<f64, 2> for RidgedMulti<T> where T: Bounded<f64, 2> + Default + Seedable {
+    fn min(&self) -> f64 {
+        self.bounds().0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds().1
+    }
+
+    /// Every signal is (1 - |s|)² * weight, the weight is 1 for the first octave and in [0, 1] for the others.
+    /// The result r becomes |r| * 2 / (2 - 0.5^(octaves - 1)) - 1
+    fn bounds(&self) -> (f64, f64) {
+        let (low, high) = abs(T::default().bounds());
+        let ridge = abs((1. - high, 1. - low));
+        let ridge = (ridge.0 * ridge.0, ridge.1 * ridge.1);
+        let sum = (0..self.octaves)
+            .map(|x| scale(if x == 0 { ridge } else { (0., ridge.1) }, self.persistence.powi(x as i32)))
+            .fold((0., 0.), |sum, s| (sum.0 + s.0, sum.1 + s.1));
+        let result = scale(abs(sum), 2. / (2. - 0.5_f64.powi(self.octaves as i32 - 1)));
+        (result.0 - 1., result.1 - 1.)
+    }
+}
+
+impl<F> Bounded<f64, 2> for ScaleBias<f64, F, 2> where F: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        scale(self.source.bounds(), self.scale).0 + self.bias
+    }
+
+    fn max(&self) -> f64 {
+        scale(self.source.bounds(), self.scale).1 + self.bias
+    }
+}
+
+impl<F> Bounded<f64, 2> for Exponent<f64, F, 2> where F: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.bounds().0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds().1
+    }
+
+    /// |(s + 1) / 2|^exponent * 2 - 1, x^e is monotonic for x >= 0
+    fn bounds(&self) -> (f64, f64) {
+        let (low, high) = scale(self.source.bounds(), 0.5);
+        let (low, high) = abs((low + 0.5, high + 0.5));
+        let (low, high) = min_max([low.powf(self.exponent), high.powf(self.exponent)]);
+        (low * 2. - 1., high * 2. - 1.)
+    }
+}
+
+/// Turbulence moves the input point only: the output is always a value of the source
+impl<S, F> Bounded<f64, 2> for Turbulence<S, F> where S: Bounded<f64, 2>, F: Default + Seedable + NoiseFn<f64, 2> {
+    fn min(&self) -> f64 {
+        self.source.min()
+    }
+
+    fn max(&self) -> f64 {
+        self.source.max()
+    }
+}
+
+/// The output is source1, source2 or an interpolation of the two
+impl<F, F1, C> Bounded<f64, 2> for Select<f64, F, F1, C, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2>, C: NoiseFn<f64, 2> {
+    fn min(&self) -> f64 {
+        hull(self.source1.bounds(), self.source2.bounds()).0
+    }
+
+    fn max(&self) -> f64 {
+        hull(self.source1.bounds(), self.source2.bounds()).1
+    }
+}
+
+impl<F, F1, C> Bounded<f64, 2> for Blend<f64, F, F1, C, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2>, C: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.bounds().0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds().1
+    }
+
+    /// lower + control * (upper - lower) is linear in each of the three, the extremes are at the corners.
+    /// The control isn't remapped: a control out of [0, 1] extrapolates past the sources
+    fn bounds(&self) -> (f64, f64) {
+        let (lower, upper, control) = (self.source1.bounds(), self.source2.bounds(), self.control.bounds());
+        min_max([lower.0, lower.1].into_iter().flat_map(|l| {
+            [upper.0, upper.1].into_iter().flat_map(move |u| [control.0, control.1].map(|c| l + c * (u - l)))
+        }))
+    }
+}
+
+impl<F, F1> Bounded<f64, 2> for Min<f64, F, F1, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.source1.min().min(self.source2.min())
+    }
+
+    fn max(&self) -> f64 {
+        self.source1.max().min(self.source2.max())
+    }
+}
+
+impl<F, F1> Bounded<f64, 2> for Max<f64, F, F1, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.source1.min().max(self.source2.min())
+    }
+
+    fn max(&self) -> f64 {
+        self.source1.max().max(self.source2.max())
+    }
+}
+
+impl<F, F1> Bounded<f64, 2> for Power<f64, F, F1, 2> where F: Bounded<f64, 2>, F1: Bounded<f64, 2> {
+    fn min(&self) -> f64 {
+        self.bounds().0
+    }
+
+    fn max(&self) -> f64 {
+        self.bounds().1
+    }
+
+    /// base^exponent. With a base >= 0 it's monotonic in both, the extremes are at the corners.
+    /// A negative base is defined only for an integer exponent: with a constant one x^n is bounded
+    /// by its ends (and by 0 if the base crosses it), otherwise the output may be NaN and the bounds are infinite
+    fn bounds(&self) -> (f64, f64) {
+        let (base, exponent) = (self.source1.bounds(), self.source2.bounds());
+        if base.0 >= 0. {
+            min_max([base.0, base.1].into_iter().flat_map(|b| [exponent.0, exponent.1].map(|e| b.powf(e))))
+        } else if exponent.0 == exponent.1 && exponent.0.fract() == 0. && exponent.0 >= 0. {
+            let n = exponent.0;
+            let ends = min_max([base.0.powf(n), base.1.powf(n)]);
+            if n > 0. && base.1 > 0. {
+                hull(ends, (0., 0.))
+            } else {
+                ends
+            }
+        } else {
+            (f64::NEG_INFINITY, f64::INFINITY)
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use noise::{NoiseFn, Perlin};
+use rand::{rngs::StdRng, SeedableRng};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::{pathfinding::build_road, tile::PreTileType, vector2::Vector2};
+
+use super::{noise_bundle::NoiseBundle, road::{decorate_road, RoadConfig}};
+
+/// Side of the cells of the road grid, every cell has a hub connected to the
+/// hubs of the 4 neighbouring cells
+const ROAD_CELL: i64 = 64;
+/// Hubs are never placed closer than this to the border of their cell
+const HUB_MARGIN: i64 = 8;
+/// Tiles spanned by a unit of the noise that picks the TileType inside a band
+const SELECTOR_PERIOD: f64 = 48.0;
+
+/// Rectangle of the unbounded world: origin (x, y) and width x height
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub x: i64,
+    pub y: i64,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: i64, y: i64, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width as i64 && y < self.y + self.height as i64
+    }
+}
+
+/// Generate the tiles of a region of the unbounded world.
+///
+/// Every step depends only on the world coordinates and the seed, never on the region:
+/// - TileType: the noise band, if the band has more TileTypes a low frequency noise picks one
+/// - Content: a StdRng seeded with the hash of the tile coordinates
+/// - Roads: every cell of the road grid has a hub, the hubs of adjacent cells are linked
+///   by a path computed on the terrain of the two cells only
+///
+/// so adjacent chunks stitch seamlessly. The roads follow the slope penalties of the RoadConfig,
+/// they are not graded: the grading depends on the whole road network.
+pub(crate) fn build_chunk(noise_bundle: &NoiseBundle, elevation_factor: f64, not_spawnable: &[TileType], roads: &RoadConfig, region: Region) -> Vec<Vec<Tile>> {
+    let mut map = terrain(noise_bundle, elevation_factor, region);
+
+    let biomes = noise_bundle.get_biomes().map(|table| table.sampler(noise_bundle.get_seed()));
+    for (offset_x, column) in map.iter_mut().enumerate() {
+        for (offset_y, tile) in column.iter_mut().enumerate() {
+            let (x, y) = (region.x + offset_x as i64, region.y + offset_y as i64);
+            let mut rng = StdRng::seed_from_u64(tile_hash(noise_bundle.get_seed(), x, y, 0));
+            let elevation = (noise_bundle.equalized_tile(x as f64, y as f64) * 100.0).max(0.0) as usize;
+            let biome = biomes.as_ref().and_then(|biomes| biomes.biome(x as f64, y as f64, elevation));
+            tile.content = match biome.and_then(|biome| biome.put_content(&mut rng)) {
+                Some(content) => content,
+                None => noise_bundle.put_content(tile.tile_type, &mut rng),
+            };
+        }
+    }
+
+    // Every edge whose two cells touch the region, the cell before the first one included
+    let (first_x, first_y) = (region.x.div_euclid(ROAD_CELL) - 1, region.y.div_euclid(ROAD_CELL) - 1);
+    let last_x = (region.x + region.width as i64).div_euclid(ROAD_CELL);
+    let last_y = (region.y + region.height as i64).div_euclid(ROAD_CELL);
+    for cell_x in first_x..=last_x {
+        for cell_y in first_y..=last_y {
+            for (next_x, next_y) in [(cell_x + 1, cell_y), (cell_x, cell_y + 1)] {
+                let window = Region::new(
+                    cell_x * ROAD_CELL,
+                    cell_y * ROAD_CELL,
+                    ((next_x - cell_x + 1) * ROAD_CELL) as usize,
+                    ((next_y - cell_y + 1) * ROAD_CELL) as usize,
+                );
+                if window.x >= region.x + region.width as i64 || window.y >= region.y + region.height as i64
+                    || window.x + window.width as i64 <= region.x || window.y + window.height as i64 <= region.y {
+                    continue;
+                }
+                paint_road(noise_bundle, elevation_factor, not_spawnable, roads, region, window, &mut map,
+                           hub(noise_bundle.get_seed(), cell_x, cell_y), hub(noise_bundle.get_seed(), next_x, next_y));
+            }
+        }
+    }
+    map
+}
+
+/// TileType and elevation of every tile of the region, no content
+fn terrain(noise_bundle: &NoiseBundle, elevation_factor: f64, region: Region) -> Vec<Vec<Tile>> {
+    let selector = Perlin::new(noise_bundle.get_seed().wrapping_add(1));
+    let distribution = noise_bundle.get_noise_distribution();
+    let biomes = noise_bundle.get_biomes().map(|table| table.sampler(noise_bundle.get_seed()));
+
+    (0..region.width).map(|offset_x| {
+        (0..region.height).map(|offset_y| {
+            let (x, y) = ((region.x + offset_x as i64) as f64, (region.y + offset_y as i64) as f64);
+            let elevation = noise_bundle.equalized_tile(x, y);
+            let pretile = noise_bundle.noise_to_pretile((elevation * 100.0).max(0.0) as usize);
+            let tile_type = match pretile.pre_tiletype {
+                PreTileType::Zone(id) => {
+                    let types = &distribution.bands[id].1;
+                    let pick = (selector.get([x / SELECTOR_PERIOD, y / SELECTOR_PERIOD]) + 1.0) / 2.0;
+                    types[((pick * types.len() as f64) as usize).min(types.len() - 1)]
+                }
+                PreTileType::Void => TileType::ShallowWater,
+            };
+            let tile_type = biomes.as_ref()
+                .and_then(|biomes| biomes.biome(x, y, pretile.elevation))
+                .map_or(tile_type, |biome| biome.get_tile_type());
+            Tile {
+                tile_type,
+                content: Content::None,
+                elevation: ((pretile.elevation as f64 / 100.0) * elevation_factor) as usize,
+            }
+        }).collect()
+    }).collect()
+}
+
+/// Route the road between two hubs on the terrain of window, paint the tiles inside region
+#[allow(clippy::too_many_arguments)]
+fn paint_road(noise_bundle: &NoiseBundle, elevation_factor: f64, not_spawnable: &[TileType], roads: &RoadConfig, region: Region, window: Region,
+              map: &mut [Vec<Tile>], start: (i64, i64), end: (i64, i64)) {
+    let mut window_map = terrain(noise_bundle, elevation_factor, window);
+    let local = |(x, y): (i64, i64)| Vector2::new((x - window.x) as usize, (y - window.y) as usize);
+    let (start, end) = (local(start), local(end));
+
+    // Same rule of WorldGenerator::build: lavapools and deepwater aren't connected
+    if not_spawnable.contains(&window_map[start.x][start.y].tile_type) || not_spawnable.contains(&window_map[end.x][end.y].tile_type) {
+        return;
+    }
+
+    let mut rng = StdRng::seed_from_u64(tile_hash(noise_bundle.get_seed(), window.x, window.y, window.width as u64));
+    if let Ok(path) = roads.finder().find_with_rng(&window_map, start, end, &mut rng) {
+        // Same content of a tile for every road crossing it, whatever the chunk
+        build_road(&mut window_map, &path, |p| {
+            let mut rng = StdRng::seed_from_u64(tile_hash(noise_bundle.get_seed(), window.x + p.x as i64, window.y + p.y as i64, 2));
+            noise_bundle.put_content(TileType::Street, &mut rng)
+        });
+        decorate_road(&mut window_map, &path, roads);
+        for p in path {
+            let (x, y) = (window.x + p.x as i64, window.y + p.y as i64);
+            if region.contains(x, y) {
+                let tile = &mut map[(x - region.x) as usize][(y - region.y) as usize];
+                tile.tile_type = window_map[p.x][p.y].tile_type;
+                tile.content = window_map[p.x][p.y].content.clone();
+            }
+        }
+    }
+}
+
+/// World coordinates of the hub of a cell of the road grid
+fn hub(seed: u32, cell_x: i64, cell_y: i64) -> (i64, i64) {
+    let hash = tile_hash(seed, cell_x, cell_y, 1);
+    let span = (ROAD_CELL - 2 * HUB_MARGIN) as u64;
+    (
+        cell_x * ROAD_CELL + HUB_MARGIN + (hash % span) as i64,
+        cell_y * ROAD_CELL + HUB_MARGIN + ((hash >> 32) % span) as i64,
+    )
+}
+
+/// splitmix64 of the seed, the coordinates and a salt, used to seed per tile randomness
+fn tile_hash(seed: u32, x: i64, y: i64, salt: u64) -> u64 {
+    fn mix(mut z: u64) -> u64 {
+        z = z.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+    mix(seed as u64 ^ mix(x as u64 ^ mix(y as u64 ^ mix(salt))))
+}
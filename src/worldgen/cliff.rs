@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+
+use rand::Rng;
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::{generator_error::GeneratorError, vector2::Vector2};
+
+/// Parameters of the cliff pass, the sharp elevation breaks of the heightmap
+/// become lines of Wall tiles broken by gaps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliffConfig {
+    /// Minimum elevation drop, in [0.0, 1.0], between two adjacent tiles to raise a wall
+    /// on the higher one
+    threshold: f64,
+    /// Longest run of Wall tiles along a cliff, a gap is opened after it
+    max_length: usize,
+    /// Chance, in [0.0, 1.0], that a tile of a cliff opens a gap before max_length
+    gap_frequency: f64,
+    /// Tiles of a cliff left open by every gap
+    gap_width: usize,
+}
+
+impl Default for CliffConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0.01,
+            max_length: 24,
+            gap_frequency: 0.05,
+            gap_width: 2,
+        }
+    }
+}
+
+impl CliffConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn set_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    pub fn set_gap_frequency(mut self, gap_frequency: f64) -> Self {
+        self.gap_frequency = gap_frequency;
+        self
+    }
+
+    pub fn set_gap_width(mut self, gap_width: usize) -> Self {
+        self.gap_width = gap_width;
+        self
+    }
+
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if !self.threshold.is_finite() || self.threshold < 0.0 {
+            return Err(GeneratorError::InvalidParameter("cliffs.threshold".to_string()));
+        }
+        if !(0.0..=1.0).contains(&self.gap_frequency) {
+            return Err(GeneratorError::InvalidParameter("cliffs.gap_frequency".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Cliffs are raised only on dry land, water, lava and teleports are left untouched
+fn can_be_wall(tile_type: TileType) -> bool {
+    matches!(tile_type, TileType::Grass | TileType::Sand | TileType::Hill | TileType::Mountain | TileType::Snow)
+}
+
+/// 8-neighbours of p inside a width x height grid
+fn neighbours8(p: Vector2, width: usize, height: usize) -> impl Iterator<Item = Vector2> {
+    (-1i64..=1).flat_map(|dx| (-1i64..=1).map(move |dy| (dx, dy)))
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .map(move |(dx, dy)| (p.x as i64 + dx, p.y as i64 + dy))
+        .filter(move |&(x, y)| x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height)
+        .map(|(x, y)| Vector2::new(x as usize, y as usize))
+}
+
+/// Open the tiles of the cliff crossed by the steepest path through p, downhill and uphill:
+/// on a cliff thicker than one tile the gap becomes a passage from the bottom to the top
+fn open_passage(p: Vector2, heightmap: &[Vec<f64>], cliff: &[Vec<bool>], open: &mut [Vec<bool>]) {
+    let (width, height) = (heightmap.len(), heightmap[0].len());
+    open[p.x][p.y] = true;
+    for downhill in [true, false] {
+        let steeper = |a: &Vector2, b: &Vector2| {
+            let order = heightmap[a.x][a.y].total_cmp(&heightmap[b.x][b.y]);
+            if downhill { order.reverse() } else { order }
+        };
+        let mut q = p;
+        while let Some(next) = q.neighbours(width, height).into_iter().max_by(steeper) {
+            let drop = heightmap[q.x][q.y] - heightmap[next.x][next.y];
+            if (if downhill { drop } else { -drop }) <= 0.0 || !cliff[next.x][next.y] || open[next.x][next.y] {
+                break;
+            }
+            open[next.x][next.y] = true;
+            q = next;
+        }
+    }
+}
+
+/// Raise the cliffs of the heightmap as Wall tiles.
+/// A tile is on a cliff if it's higher than one of its 4-neighbours by more than the threshold,
+/// the steep slopes make cliffs a few tiles thick. The 8-connected cliffs are walked from
+/// one of their ends, every max_length walls (or at random with gap_frequency) gap_width
+/// tiles are left open, and every gap tile opens a passage along the gradient,
+/// so the two sides of a cliff stay connected.
+pub(crate) fn raise_cliffs<R: Rng>(map: &mut [Vec<Tile>], heightmap: &[Vec<f64>], config: &CliffConfig, rng: &mut R) {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let mut cliff = vec![vec![false; height]; width];
+    for x in 0..width {
+        for y in 0..height {
+            cliff[x][y] = can_be_wall(map[x][y].tile_type) && Vector2::new(x, y).neighbours(width, height).iter()
+                .any(|n| heightmap[x][y] - heightmap[n.x][n.y] > config.threshold);
+        }
+    }
+
+    let cliff = &cliff;
+    let degree = |p: Vector2| neighbours8(p, width, height).filter(|n| cliff[n.x][n.y]).count();
+    let mut visited = vec![vec![false; height]; width];
+    let mut walked = vec![vec![false; height]; width];
+    let mut wall = vec![vec![false; height]; width];
+    let mut open = vec![vec![false; height]; width];
+    for x in 0..width {
+        for y in 0..height {
+            if visited[x][y] || !cliff[x][y] {
+                continue;
+            }
+            // Collect the line, then walk it again from its end with the fewest cliff neighbours
+            let mut line = Vec::<Vector2>::new();
+            let mut queue = VecDeque::from([Vector2::new(x, y)]);
+            visited[x][y] = true;
+            while let Some(p) = queue.pop_front() {
+                line.push(p);
+                for n in neighbours8(p, width, height) {
+                    if cliff[n.x][n.y] && !visited[n.x][n.y] {
+                        visited[n.x][n.y] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+            let start = *line.iter().min_by_key(|p| degree(**p)).unwrap();
+
+            let mut queue = VecDeque::from([start]);
+            walked[start.x][start.y] = true;
+            let (mut run, mut gap) = (0, 0);
+            while let Some(p) = queue.pop_front() {
+                if gap > 0 {
+                    gap -= 1;
+                    open_passage(p, heightmap, cliff, &mut open);
+                } else if run >= config.max_length || rng.gen_bool(config.gap_frequency) {
+                    run = 0;
+                    gap = config.gap_width.saturating_sub(1);
+                    open_passage(p, heightmap, cliff, &mut open);
+                } else {
+                    run += 1;
+                    wall[p.x][p.y] = true;
+                }
+                for n in neighbours8(p, width, height) {
+                    if cliff[n.x][n.y] && !walked[n.x][n.y] {
+                        walked[n.x][n.y] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+    }
+
+    // The passages may cross walls raised before their gap was reached
+    for x in 0..width {
+        for y in 0..height {
+            if wall[x][y] && !open[x][y] {
+                map[x][y].tile_type = TileType::Wall;
+                map[x][y].content = Content::None;
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use noise::{NoiseFn, Perlin};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::{generator_error::GeneratorError, vector2::Vector2};
+
+/// Rearranges some contents in coherent clusters (forests, rock fields, coin deposits)
+/// instead of the salt-and-pepper of the independent rolls of the ContentDist.
+/// The number of every clustered content on every TileType is left untouched,
+/// so the frequencies of the ContentDist are respected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentClustering {
+    /// Contents to cluster, compared by kind (the quantity doesn't matter)
+    contents: Vec<Content>,
+    /// Tiles spanned by a unit of the noise of a cluster, the bigger the wider the clusters
+    period: f64,
+}
+
+impl Default for ContentClustering {
+    fn default() -> Self {
+        Self {
+            contents: [Content::Tree(0), Content::Rock(0), Content::Bush(0), Content::Coin(0)]
+                .iter().map(|content| content.to_default()).collect(),
+            period: 24.0,
+        }
+    }
+}
+
+impl ContentClustering {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_contents(mut self, contents: Vec<Content>) -> Self {
+        self.contents = contents.iter().map(|content| content.to_default()).collect();
+        self
+    }
+
+    pub fn set_period(mut self, period: f64) -> Self {
+        self.period = period;
+        self
+    }
+
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if !self.period.is_finite() || self.period <= 0.0 {
+            return Err(GeneratorError::InvalidParameter("content_clustering.period".to_string()));
+        }
+        Ok(())
+    }
+
+    fn index_of(&self, content: &Content) -> Option<usize> {
+        let kind = content.to_default();
+        self.contents.iter().position(|c| *c == kind)
+    }
+}
+
+/// Every clustered content has its own noise field. For every TileType the clustered contents
+/// are removed and put back, one kind at a time, on the free tiles with the highest noise:
+/// the same amount of them ends up grouped around the peaks of its field.
+pub(crate) fn cluster_contents(map: &mut [Vec<Tile>], clustering: &ContentClustering, seed: u32) {
+    let fields: Vec<Perlin> = (0..clustering.contents.len())
+        .map(|i| Perlin::new(seed.wrapping_add(10 + i as u32)))
+        .collect();
+
+    // Tiles that can receive a clustered content: the empty ones and the clustered ones
+    let mut groups: HashMap<TileType, Vec<Vector2>> = HashMap::new();
+    for (x, column) in map.iter().enumerate() {
+        for (y, tile) in column.iter().enumerate() {
+            if tile.content == Content::None || clustering.index_of(&tile.content).is_some() {
+                groups.entry(tile.tile_type).or_default().push(Vector2::new(x, y));
+            }
+        }
+    }
+
+    // Every TileType is independent from the others, the HashMap order doesn't matter
+    for tiles in groups.values() {
+        let mut pulled: Vec<Vec<Content>> = vec![vec![]; clustering.contents.len()];
+        for p in tiles {
+            if let Some(i) = clustering.index_of(&map[p.x][p.y].content) {
+                pulled[i].push(map[p.x][p.y].content.clone());
+                map[p.x][p.y].content = Content::None;
+            }
+        }
+
+        let mut free = vec![true; tiles.len()];
+        for (i, contents) in pulled.into_iter().enumerate() {
+            if contents.is_empty() {
+                continue;
+            }
+            let value = |p: &Vector2| fields[i].get([p.x as f64 / clustering.period, p.y as f64 / clustering.period]);
+            let mut order: Vec<(usize, f64)> = (0..tiles.len())
+                .filter(|k| free[*k])
+                .map(|k| (k, value(&tiles[k])))
+                .collect();
+            order.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            for ((k, _), content) in order.into_iter().zip(contents) {
+                map[tiles[k].x][tiles[k].y].content = content;
+                free[k] = false;
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use robotics_lib::world::tile::{Tile, TileType};
+
+use crate::utils::generator_error::GeneratorError;
+
+use super::noise_bundle::NoiseDistribution;
+
+/// Target fraction of the map covered by groups of TileTypes, like 40% water and 10% mountains.
+/// The groups go from the lowest to the highest elevation: the generator puts the band boundaries
+/// of the noise_distribution at the quantiles of the heightmap, the elevations are left as they are.
+/// The zones of a group pick one of its TileTypes like the ones of a band.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CoverageTargets {
+    groups: Vec<(Vec<TileType>, f64)>,
+}
+
+/// Requested and actual coverage of a group in the generated world
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub tile_types: Vec<TileType>,
+    pub requested: f64,
+    /// Fraction of the tiles of the final map with one of the tile_types,
+    /// the roads, rivers and the other passes after the terrain make it differ from the requested one
+    pub actual: f64,
+}
+
+impl CoverageTargets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the group above the ones already added, fraction in (0.0, 1.0]
+    pub fn add_group(mut self, tile_types: Vec<TileType>, fraction: f64) -> Self {
+        self.groups.push((tile_types, fraction));
+        self
+    }
+
+    /*
+    checks these conditions:
+    - every fraction is positive and they add up to 1.0
+    - a TileType belongs to one group at most
+    - at most 100 groups, one per elevation percent
+    - the derived noise_distribution is valid (no empty group, no Wall, no active teleports)
+    */
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        let invalid = || Err(GeneratorError::InvalidParameter("coverage".to_string()));
+        if self.groups.is_empty() || self.groups.len() > 100 {
+            return invalid();
+        }
+        if self.groups.iter().any(|(_, fraction)| !fraction.is_finite() || *fraction <= 0.0) {
+            return invalid();
+        }
+        if (self.groups.iter().map(|(_, fraction)| fraction).sum::<f64>() - 1.0).abs() > 1e-6 {
+            return invalid();
+        }
+        let mut seen = HashSet::new();
+        if !self.groups.iter().flat_map(|(types, _)| types.iter()).all(|t| seen.insert(*t)) {
+            return invalid();
+        }
+        self.distribution().is_valid()
+    }
+
+    /// Band boundaries in [0, 101]: the cumulative fractions in percent, every band at least 1 wide
+    fn boundaries(&self) -> Vec<usize> {
+        let n = self.groups.len();
+        let mut boundaries = vec![0];
+        let mut cumulative = 0.0;
+        for (k, (_, fraction)) in self.groups.iter().enumerate().take(n.saturating_sub(1)) {
+            cumulative += fraction;
+            let previous = boundaries[k];
+            boundaries.push(((cumulative * 100.0).round() as usize).clamp(previous + 1, 100 - (n - 2 - k)));
+        }
+        boundaries.push(101);
+        boundaries
+    }
+
+    /// noise_distribution with a band per group cut at the requested fractions,
+    /// the one checked by is_valid: apply() moves the boundaries but keeps the groups
+    pub(crate) fn distribution(&self) -> NoiseDistribution {
+        let boundaries = self.boundaries();
+        NoiseDistribution::new(
+            self.groups
+                .iter()
+                .enumerate()
+                .map(|(k, (types, _))| (boundaries[k]..boundaries[k + 1], types.clone()))
+                .collect(),
+        )
+    }
+
+    /// noise_distribution whose bands cover the requested fractions of the heightmap.
+    /// The bands are cut at the elevation percents closest to the quantiles of the fractions,
+    /// so the coverage is as precise as the 1% steps of the bands allow
+    pub(crate) fn apply(&self, heightmap: &[Vec<f64>]) -> NoiseDistribution {
+        // below[p]: tiles with an elevation percent lower than p, the percent the preworld puts them in
+        let mut histogram = [0usize; 101];
+        for elevation in heightmap.iter().flatten() {
+            histogram[((elevation * 100.0) as usize).min(100)] += 1;
+        }
+        let mut below = vec![0; 102];
+        for (p, count) in histogram.iter().enumerate() {
+            below[p + 1] = below[p] + count;
+        }
+        let total = below[101].max(1) as f64;
+
+        let n = self.groups.len();
+        let mut boundaries = vec![0];
+        let mut cumulative = 0.0;
+        for (k, (_, fraction)) in self.groups.iter().enumerate().take(n.saturating_sub(1)) {
+            cumulative += fraction;
+            // Every band at least 1 wide, room left for the ones above
+            let boundary = (boundaries[k] + 1..=100 - (n - 2 - k))
+                .min_by(|a, b| (below[*a] as f64 / total - cumulative).abs().total_cmp(&(below[*b] as f64 / total - cumulative).abs()))
+                .unwrap_or(boundaries[k] + 1);
+            boundaries.push(boundary);
+        }
+        boundaries.push(101);
+        NoiseDistribution::new(
+            self.groups
+                .iter()
+                .enumerate()
+                .map(|(k, (types, _))| (boundaries[k]..boundaries[k + 1], types.clone()))
+                .collect(),
+        )
+    }
+
+    /// Actual coverage of every group in the map
+    pub(crate) fn measure(&self, map: &[Vec<Tile>]) -> Vec<Coverage> {
+        let total = map.iter().map(|column| column.len()).sum::<usize>().max(1) as f64;
+        self.groups
+            .iter()
+            .map(|(tile_types, fraction)| Coverage {
+                tile_types: tile_types.clone(),
+                requested: *fraction,
+                actual: map.iter().flatten().filter(|tile| tile_types.contains(&tile.tile_type)).count() as f64 / total,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,206 @@
+use crate::utils::generator_error::GeneratorError;
+
+/// Parameters of the erosion stage, applied to the continuous heightmap
+/// before it's quantized in the zones of the noise_distribution.
+/// Elevations are the normalized ones, in [0.0, 1.0].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErosionConfig {
+    /// Rounds of hydraulic + thermal erosion
+    iterations: usize,
+    /// Water added to every tile at every round
+    rain_amount: f64,
+    /// Fraction of the water that dissolves the terrain into sediment
+    solubility: f64,
+    /// Fraction of the water that evaporates at every round
+    evaporation: f64,
+    /// Sediment carried by a unit of water, the excess is deposited
+    sediment_capacity: f64,
+    /// Maximum stable elevation difference between two adjacent tiles,
+    /// the material above it slides down (thermal erosion)
+    talus_angle: f64,
+    /// Fraction of the material above the talus angle that slides at every round
+    thermal_rate: f64,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 50,
+            rain_amount: 0.01,
+            solubility: 0.01,
+            evaporation: 0.5,
+            sediment_capacity: 0.01,
+            talus_angle: 0.01,
+            thermal_rate: 0.5,
+        }
+    }
+}
+
+impl ErosionConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn set_rain_amount(mut self, rain_amount: f64) -> Self {
+        self.rain_amount = rain_amount;
+        self
+    }
+
+    pub fn set_solubility(mut self, solubility: f64) -> Self {
+        self.solubility = solubility;
+        self
+    }
+
+    pub fn set_evaporation(mut self, evaporation: f64) -> Self {
+        self.evaporation = evaporation;
+        self
+    }
+
+    pub fn set_sediment_capacity(mut self, sediment_capacity: f64) -> Self {
+        self.sediment_capacity = sediment_capacity;
+        self
+    }
+
+    pub fn set_talus_angle(mut self, talus_angle: f64) -> Self {
+        self.talus_angle = talus_angle;
+        self
+    }
+
+    pub fn set_thermal_rate(mut self, thermal_rate: f64) -> Self {
+        self.thermal_rate = thermal_rate;
+        self
+    }
+
+    /// The amounts are finite and not negative, the fractions are in [0.0, 1.0]
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        let amounts = [("erosion.rain_amount", self.rain_amount), ("erosion.sediment_capacity", self.sediment_capacity),
+                       ("erosion.talus_angle", self.talus_angle)];
+        let fractions = [("erosion.solubility", self.solubility), ("erosion.evaporation", self.evaporation),
+                         ("erosion.thermal_rate", self.thermal_rate)];
+        for (name, amount) in amounts {
+            if !amount.is_finite() || amount < 0.0 {
+                return Err(GeneratorError::InvalidParameter(name.to_string()));
+            }
+        }
+        for (name, fraction) in fractions {
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(GeneratorError::InvalidParameter(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 4-neighbours of (x, y) inside a width x height grid
+fn neighbours(x: usize, y: usize, width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+    [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+        .into_iter()
+        .filter(move |(nx, ny)| *nx < width && *ny < height)
+}
+
+/// Erode the heightmap in place.
+/// Every round rains on the map, the water dissolves the terrain and flows downhill
+/// carrying the sediment, which is deposited where the water slows down or evaporates.
+/// Then the slopes steeper than the talus angle collapse.
+/// Every round reads the previous state only, the result doesn't depend on the visit order.
+pub(crate) fn erode(heightmap: &mut [Vec<f64>], config: &ErosionConfig) {
+    if heightmap.is_empty() || heightmap[0].is_empty() {
+        return;
+    }
+    let (width, height) = (heightmap.len(), heightmap[0].len());
+    let mut water = vec![vec![0.0; height]; width];
+    let mut sediment = vec![vec![0.0; height]; width];
+
+    for _ in 0..config.iterations {
+        // Rain and dissolve
+        for x in 0..width {
+            for y in 0..height {
+                water[x][y] += config.rain_amount;
+                let dissolved = config.solubility * water[x][y];
+                heightmap[x][y] -= dissolved;
+                sediment[x][y] += dissolved;
+            }
+        }
+
+        // Water and sediment flow to the lower neighbours, proportionally to the drop
+        let mut water_delta = vec![vec![0.0; height]; width];
+        let mut sediment_delta = vec![vec![0.0; height]; width];
+        for x in 0..width {
+            for y in 0..height {
+                if water[x][y] <= 0.0 {
+                    continue;
+                }
+                let surface = heightmap[x][y] + water[x][y];
+                let (mut drops, mut count) = ([((0, 0), 0.0); 4], 0);
+                for (nx, ny) in neighbours(x, y, width, height) {
+                    let drop = surface - heightmap[nx][ny] - water[nx][ny];
+                    if drop > 0.0 {
+                        drops[count] = ((nx, ny), drop);
+                        count += 1;
+                    }
+                }
+                let drops = &drops[..count];
+                let total: f64 = drops.iter().map(|(_, drop)| drop).sum();
+                let max_drop = drops.iter().map(|(_, drop)| *drop).fold(0.0, f64::max);
+                if total <= 0.0 {
+                    continue;
+                }
+                let moved = water[x][y].min(max_drop / 2.0);
+                let moved_sediment = sediment[x][y] * moved / water[x][y];
+                for &((nx, ny), drop) in drops {
+                    water_delta[nx][ny] += moved * drop / total;
+                    sediment_delta[nx][ny] += moved_sediment * drop / total;
+                }
+                water_delta[x][y] -= moved;
+                sediment_delta[x][y] -= moved_sediment;
+            }
+        }
+
+        // Evaporate and deposit what the water can't carry anymore
+        for x in 0..width {
+            for y in 0..height {
+                water[x][y] = (water[x][y] + water_delta[x][y]) * (1.0 - config.evaporation);
+                sediment[x][y] += sediment_delta[x][y];
+                let capacity = config.sediment_capacity * water[x][y];
+                if sediment[x][y] > capacity {
+                    heightmap[x][y] += sediment[x][y] - capacity;
+                    sediment[x][y] = capacity;
+                }
+            }
+        }
+
+        // Thermal erosion, the material above the talus angle slides down
+        let mut height_delta = vec![vec![0.0; height]; width];
+        for x in 0..width {
+            for y in 0..height {
+                for (nx, ny) in neighbours(x, y, width, height) {
+                    let difference = heightmap[x][y] - heightmap[nx][ny];
+                    if difference > config.talus_angle {
+                        // Half the excess levels the pair at the talus angle, split between the 4 neighbours:
+                        // a tile never gives more than it has above them
+                        let slide = config.thermal_rate * (difference - config.talus_angle) / 8.0;
+                        height_delta[x][y] -= slide;
+                        height_delta[nx][ny] += slide;
+                    }
+                }
+            }
+        }
+        for x in 0..width {
+            for y in 0..height {
+                heightmap[x][y] += height_delta[x][y];
+            }
+        }
+    }
+
+    // The sediment still in the water settles where it is
+    for x in 0..width {
+        for y in 0..height {
+            heightmap[x][y] += sediment[x][y];
+        }
+    }
+}
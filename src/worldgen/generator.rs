@@ -1,16 +1,23 @@
-use std::{collections::HashMap};
+use std::{borrow::Cow, collections::HashMap};
 
-use rand::{seq::SliceRandom, thread_rng};
-use robotics_lib::world::{environmental_conditions::{EnvironmentalConditions, WeatherType}, tile::{Content, Tile, TileType}, world_generator::Generator};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use threadpool::ThreadPool;
+use threadpool_scope::scope_with;
+use robotics_lib::world::{environmental_conditions::{EnvironmentalConditions, WeatherType}, tile::{Content, Tile, TileType}, world_generator::{Generator, World}};
 
-use crate::utils::{pathfinding::{build_road, shortest_path}, tile::{PreTile, PreTileType}, vector2::Vector2, zone::Zone};
+use crate::utils::{generator_error::GeneratorError, graph::{delaunay, minimum_spanning_tree}, pathfinding::build_road, tile::{PreTile, PreTileType}, vector2::Vector2, zone::Zone};
 
-use super::noise_bundle::NoiseBundle;
+use super::{chunk::{build_chunk, Region}, cliff::{raise_cliffs, CliffConfig}, clustering::cluster_contents, coverage::CoverageTargets, erosion::{erode, ErosionConfig}, hydrology::{carve_rivers, RiverConfig}, noise_bundle::{equalize, NoiseBundle, NoiseDistribution}, quota::{check_max_quantity, enforce_quotas, ContentQuota}, report::GenerationReport, road::{decorate_road, grade_roads, RoadConfig}, settlement::{build_settlements, SettlementConfig}, spawn::{clear_hazards, is_spawnable, pick_spawn, SpawnStrategy}, teleport::{link_islands, TeleportConfig}};
+
+/// Columns handled by a single job of the threadpool
+const CHUNK_COLUMNS: usize = 32;
 
 /// Used to generate the World, implements the 'Generator' Trait.
 pub struct WorldGenerator {
-    /// World size (size x size) of the map 
-    size: usize,
+    /// World size (width x height) of the map,
+    /// the map is indexed as map[x][y] with x < width and y < height
+    width: usize,
+    height: usize,
     /// Elevation of tiles in range: [0, elevation_factor as usize]
     /// (normalized_noise([0.0, 1.0]) * elevation_factor) as usize
     elevation_factor: f64,
@@ -22,19 +29,69 @@ pub struct WorldGenerator {
     /// The streets will connect every homogeneous zone EXCEPT 
     /// for the ones in not_spawnable
     not_spawnable: Vec::<TileType>,
-    weather_conditions: EnvironmentalConditions
+    /// Fraction, in [0.0, 1.0], of the Delaunay edges left out of the road tree
+    /// that are built anyway, the shortest first, to make loops
+    road_loops: f64,
+    /// Slope penalties and grading of the roads
+    roads: RoadConfig,
+    /// How the robot spawn is picked, never on a not_spawnable tile
+    spawn_strategy: SpawnStrategy,
+    /// Lava and Fire are removed in this radius around the spawn
+    spawn_safe_radius: usize,
+    /// Erosion of the heightmap before it's quantized, None for the raw noise
+    erosion: Option<ErosionConfig>,
+    /// Rivers carved from the heightmap, None for no rivers
+    rivers: Option<RiverConfig>,
+    /// Wall cliffs raised on the steep slopes of the heightmap, None for no cliffs
+    cliffs: Option<CliffConfig>,
+    /// Towns built on the road junctions or near the water, None for no settlements
+    settlements: Option<SettlementConfig>,
+    /// Number of tiles holding a content, enforced at the end of the generation
+    content_quotas: Vec<(Content, ContentQuota)>,
+    /// Check every content of the World against the maximum quantity of robotics_lib (default true),
+    /// the contents with a quota are always checked
+    check_max_quantity: bool,
+    /// Threads used by the noise sampling and the tile assignment
+    threads: usize,
+    /// If None the forecast is generated from the NoiseBundle seed
+    weather_conditions: Option<EnvironmentalConditions>,
+    /// (starting_hour, time_progression, length) of the generated forecast
+    weather_forecast: (u8, u8, usize),
+    /// Teleporters linking the islands unreachable from the spawn, None for no teleporters
+    teleports: Option<TeleportConfig>,
+    /// Target coverage of groups of TileTypes, if set the noise_distribution of the
+    /// NoiseBundle is replaced by one with the bands cut at the quantiles of the heightmap
+    coverage: Option<CoverageTargets>,
+    /// Report of the last generation
+    report: GenerationReport,
 }
 
 impl Default for WorldGenerator {
     fn default() -> Self {
         Self { 
-            size: 1024, 
+            width: 1024, 
+            height: 1024, 
             elevation_factor: 1.0,
             noise_bundle: NoiseBundle::default(),
             score_table: None, 
             max_score: 420.0, 
             not_spawnable: [TileType::Lava, TileType::DeepWater].to_vec(),
-            weather_conditions:WorldGenerator::default_weather_conditions(5,10,25)
+            road_loops: 0.0,
+            roads: RoadConfig::default(),
+            spawn_strategy: SpawnStrategy::default(),
+            spawn_safe_radius: 2,
+            erosion: None,
+            rivers: None,
+            cliffs: None,
+            settlements: None,
+            content_quotas: Vec::new(),
+            check_max_quantity: true,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            weather_conditions: None,
+            weather_forecast: (5, 10, 25),
+            teleports: None,
+            coverage: None,
+            report: GenerationReport::default(),
         }
     }
 }
@@ -48,16 +105,29 @@ impl  WorldGenerator {
         Self::default()
     }
     
-    /// Set self.size: usize
+    /// Set a square (size x size) map
     pub  fn set_size(mut self, size: usize) -> Self {
         
-        self.size = size;
+        self.width = size;
+        self.height = size;
+        self
+    }
+
+    /// Set self.width: usize, the x dimension of the map
+    pub  fn set_width(mut self, width: usize) -> Self {
+        self.width = width;
         self
     }
 
-    /// get self.size: usize
-    pub  fn get_size(self) -> usize {
-        self.size
+    /// Set self.height: usize, the y dimension of the map
+    pub  fn set_height(mut self, height: usize) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// get (self.width, self.height)
+    pub  fn get_size(self) -> (usize, usize) {
+        (self.width, self.height)
     }
 
     pub  fn set_elevation_factor(mut self, elevation_factor: f64) -> Self {
@@ -70,6 +140,13 @@ impl  WorldGenerator {
         self
     }
 
+    /// Set the master seed of the NoiseBundle.
+    /// Same seed and same configuration give the same World.
+    pub  fn set_seed(mut self, seed: u32) -> Self {
+        self.noise_bundle = self.noise_bundle.set_seed(seed);
+        self
+    }
+
     pub  fn set_score_table(mut self, score_table: Option<HashMap<Content, f32>>) -> Self {
         self.score_table = score_table;
         self
@@ -84,87 +161,373 @@ impl  WorldGenerator {
         self.not_spawnable = notspawnable;
         self
     }
+    pub fn set_road_loops(mut self, road_loops: f64) -> Self {
+        self.road_loops = road_loops;
+        self
+    }
+
+    pub fn set_roads(mut self, roads: RoadConfig) -> Self {
+        self.roads = roads;
+        self
+    }
+
+    pub fn set_spawn_strategy(mut self, spawn_strategy: SpawnStrategy) -> Self {
+        self.spawn_strategy = spawn_strategy;
+        self
+    }
+
+    pub fn set_spawn_safe_radius(mut self, radius: usize) -> Self {
+        self.spawn_safe_radius = radius;
+        self
+    }
+
+    pub fn set_erosion(mut self, erosion: Option<ErosionConfig>) -> Self {
+        self.erosion = erosion;
+        self
+    }
+
+    pub fn set_rivers(mut self, rivers: Option<RiverConfig>) -> Self {
+        self.rivers = rivers;
+        self
+    }
+
+    /// Cliffs of Wall tiles, the roads route around them or through their gaps.
+    /// WorldGenerator::gen_chunk doesn't raise cliffs.
+    pub fn set_cliffs(mut self, cliffs: Option<CliffConfig>) -> Self {
+        self.cliffs = cliffs;
+        self
+    }
+
+    pub fn set_settlements(mut self, settlements: Option<SettlementConfig>) -> Self {
+        self.settlements = settlements;
+        self
+    }
+
+    /// Set the quota of a content, replacing the previous one of the same content.
+    /// The quantity doesn't matter to count, but it's the one of the contents added to meet the quota.
+    pub fn set_content_quota(mut self, content: Content, quota: ContentQuota) -> Self {
+        self.content_quotas.retain(|(c, _)| c.to_default() != content.to_default());
+        self.content_quotas.push((content, quota));
+        self
+    }
+
+    pub fn set_check_max_quantity(mut self, check_max_quantity: bool) -> Self {
+        self.check_max_quantity = check_max_quantity;
+        self
+    }
+
+    /// Threads used to generate the map, the World doesn't depend on it
+    pub fn set_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
     pub fn set_weather_conditions(mut self, conditions: EnvironmentalConditions)-> Self{
-        self.weather_conditions= conditions;
+        self.weather_conditions= Some(conditions);
+        self
+    }
+
+    /// Parameters of the forecast generated when no weather_conditions are set
+    pub fn set_weather_forecast(mut self, starting_hour: u8, time_progression: u8, length: usize)-> Self{
+        self.weather_forecast = (starting_hour, time_progression, length);
         self
     }
+
+    pub fn set_teleports(mut self, teleports: Option<TeleportConfig>) -> Self {
+        self.teleports = teleports;
+        self
+    }
+
+    /// Declare the coverage of the map instead of the noise thresholds, WorldGenerator::gen() only:
+    /// gen_chunk keeps the noise_distribution of the NoiseBundle.
+    /// Only the band boundaries move, the elevations of the tiles are the ones of the heightmap
+    pub fn set_coverage(mut self, coverage: Option<CoverageTargets>) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    /// Report of the last generation, empty before the first one
+    pub fn get_report(&self) -> &GenerationReport {
+        &self.report
+    }
 }
 
 ////////////////////////////////////////////////////////////////////
 
 impl WorldGenerator {
+    /// Generate the region [x, x + width) x [y, y + height) of the unbounded world.
+    /// Adjacent chunks stitch seamlessly in terrain, content and roads, a region
+    /// generated in one call is equal to the same region generated chunk by chunk.
+    /// NOTE: the chunks are not a crop of WorldGenerator::gen(), the zones of
+    /// an unbounded world can't be computed so the TileTypes of a band are picked by a noise.
+    /// The chunks follow the NoiseBundle (noise_fn, octaves, domain_warp, biomes, distributions)
+    /// and the routing and decorations of the RoadConfig, the stages that need the whole map are skipped:
+    /// - erosion, rivers, content_clustering, cliffs, settlements and teleports
+    /// - the content quotas, the road grading (max_step) and the road loops
+    /// - the coverage targets, the noise_distribution of the NoiseBundle is used as is
+    /// - the equalization uses the quantiles of a sample of the noise field, not the ranks of the map
+    /// - spawn and weather, there is no World to put them in
+    pub fn gen_chunk(&self, x: i64, y: i64, width: usize, height: usize) -> Vec<Vec<Tile>> {
+        build_chunk(&self.noise_bundle, self.elevation_factor, &self.not_spawnable, &self.roads, Region::new(x, y, width, height))
+    }
+
+    /// Fallible version of Generator::gen(), the configuration is validated before any work starts
+    pub fn try_gen(&mut self) -> Result<World, GeneratorError> {
+        self.validate()?;
+        let (world, report) = self.build()?;
+        self.report = report;
+        Ok(world)
+    }
+
+    /// Checks the whole configuration:
+    /// - the map is at least 1 x 1
+    /// - the bounds of the noise_fn are finite and min < max
+    /// - the noise distribution (or the coverage targets), the content distribution, the biomes and the domain warp are valid
+    /// - the configurations of the clustering, erosion, rivers, cliffs, roads, settlements and teleports are valid
+    /// - not_spawnable leaves at least one TileType of the noise distribution to spawn on
+    /// - scores and elevation_factor are finite and not negative
+    /// - the weather forecast can be generated
+    /// - the quotas have min <= max and respect the maximum quantities of robotics_lib
+    pub fn validate(&self) -> Result<(), GeneratorError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(GeneratorError::InvalidWorldSize);
+        }
+
+        let (min, max) = self.noise_bundle.get_bounds();
+        if !min.is_finite() || !max.is_finite() || min >= max {
+            return Err(GeneratorError::InvalidParameter("noise_fn".to_string()));
+        }
+
+        let noise_distribution = match &self.coverage {
+            Some(coverage) => {
+                coverage.is_valid()?;
+                Cow::Owned(coverage.distribution())
+            }
+            None => Cow::Borrowed(self.noise_bundle.get_noise_distribution()),
+        };
+        noise_distribution.is_valid()?;
+        self.noise_bundle.get_content_distribution().is_valid()?;
+        if let Some(biomes) = self.noise_bundle.get_biomes() {
+            biomes.is_valid()?;
+        }
+        if let Some(domain_warp) = self.noise_bundle.get_domain_warp() {
+            domain_warp.is_valid()?;
+        }
+        if let Some(clustering) = self.noise_bundle.get_content_clustering() {
+            clustering.is_valid()?;
+        }
+        if let Some(erosion) = &self.erosion {
+            erosion.is_valid()?;
+        }
+        if let Some(rivers) = &self.rivers {
+            rivers.is_valid()?;
+        }
+        if let Some(cliffs) = &self.cliffs {
+            cliffs.is_valid()?;
+        }
+        self.roads.is_valid()?;
+        if let Some(settlements) = &self.settlements {
+            settlements.is_valid()?;
+        }
+        if let Some(teleports) = &self.teleports {
+            teleports.is_valid()?;
+        }
+
+        if noise_distribution.bands.iter().flat_map(|(_, types)| types.iter()).all(|t| self.not_spawnable.contains(t)) {
+            return Err(GeneratorError::InvalidNotSpawnable);
+        }
+
+        if !self.elevation_factor.is_finite() || self.elevation_factor < 0.0 {
+            return Err(GeneratorError::InvalidParameter("elevation_factor".to_string()));
+        }
+        if !(0.0..=1.0).contains(&self.road_loops) {
+            return Err(GeneratorError::InvalidParameter("road_loops".to_string()));
+        }
+        if !self.max_score.is_finite() || self.max_score < 0.0 {
+            return Err(GeneratorError::InvalidScoreTable);
+        }
+        if let Some(score_table) = &self.score_table {
+            if score_table.values().any(|score| !score.is_finite() || *score < 0.0) {
+                return Err(GeneratorError::InvalidScoreTable);
+            }
+        }
+
+        if self.weather_conditions.is_none() {
+            let (starting_hour, time_progression, length) = self.weather_forecast;
+            WorldGenerator::default_weather_conditions(starting_hour, time_progression, length, &mut self.noise_bundle.rng())?;
+        }
+
+        for (content, quota) in self.content_quotas.iter() {
+            if quota.min() > quota.max() {
+                return Err(GeneratorError::InvalidQuota(content.clone()));
+            }
+            check_max_quantity(content)?;
+        }
+        Ok(())
+    }
+
     /// Internal computation of WorldGenerator::gen()
-    fn build(&self) -> (
-        Vec<Vec<Tile>>,
-        (usize, usize),
-        EnvironmentalConditions,
-        f32,
-        Option<HashMap<Content, f32>>,
-    ) {
+    fn build(&self) -> Result<(World, GenerationReport), GeneratorError> {
         // Assume create a basic map with only Grass and no content.
         // Used for a "fault-tolerant" approach
         let default_tile = Tile {
             tile_type:TileType::Teleport(false), 
             content: Content::None, elevation: 0};
 
-        let mut map: Vec::<Vec::<Tile>> = vec![vec![default_tile; self.size]; self.size];
+        let mut map: Vec::<Vec::<Tile>> = vec![vec![default_tile; self.height]; self.width];
+
+        let pool = ThreadPool::new(self.threads.max(1));
+
+        // Every random choice of the generation comes from here, in a fixed order
+        let mut rng = self.noise_bundle.rng();
+        let weather_conditions = match &self.weather_conditions {
+            Some(conditions) => conditions.clone(),
+            None => {
+                let (starting_hour, time_progression, length) = self.weather_forecast;
+                WorldGenerator::default_weather_conditions(starting_hour, time_progression, length, &mut rng)?
+            }
+        };
         
         // Pretile: Zone(id) 
         // Matrix of Pretile based on NoiseBundle.tile_distribution
         // Editing the tile distribution should be approached carefully.
-        let mut preworld = self.generate_preworld();
+        let mut heightmap = self.generate_heightmap(&pool);
+        if let Some(erosion) = &self.erosion {
+            erode(&mut heightmap, erosion);
+        }
+        if self.noise_bundle.is_equalized() {
+            equalize(&mut heightmap);
+        }
+        // With the coverage targets the bands are cut at the quantiles of the heightmap
+        let noise_distribution = match &self.coverage {
+            Some(coverage) => Cow::Owned(coverage.apply(&heightmap)),
+            None => Cow::Borrowed(self.noise_bundle.get_noise_distribution()),
+        };
+        let mut preworld = self.generate_preworld(&heightmap, &noise_distribution);
 
         // Vector of <Zone>, 
-        let zones = self.get_zones(&mut preworld);
-        let mut centroids = Vec::<Vector2>::new();
+        let zones = self.get_zones(&mut preworld, &noise_distribution);
+        let mut zone_centroids = Vec::<Vector2>::new();
 
-        for zone in 0..zones.len() {
-            for i in &zones[zone] {
+        for (zone, band_zones) in zones.iter().enumerate() {
+            for i in band_zones {
                 // Generate the TileType for the entire contiguous zone
                 // Following the rules in tile_distribution
-                let ttype: TileType = self.noise_bundle.zone_to_tiletype_dispatcher(zone);
+                let ttype: TileType = noise_distribution.zone_to_tiletype_dispatcher(zone, &mut rng);
 
                 for j in &i.inner {
                     // Set the Tiletype for every element in the zone
                     map[j.x][j.y].tile_type = ttype;
-                    // Generate the content following the rules of NoiseBundle.content_distribution
-                    map[j.x][j.y].content = self.noise_bundle.put_content(ttype);
-                    // (e in [0.0, 1.0] * f in [0.0, elevation_factor]) as usize
-                    map[j.x][j.y].elevation = ((preworld[j.x][j.y].elevation as f64 / 100.0) * self.elevation_factor) as usize;
-                }
-
-                // Avoid to connect lavapool and deepwater with the other zones of the map
-                if !self.not_spawnable.contains(&map[i.centroid.x][i.centroid.y].tile_type){
-                    centroids.push(i.centroid);
                 }
+                zone_centroids.push(i.centroid);
             }
 
         }
 
-        // Implementation constrain, to "optimize" the A* search connecting the two closer zones
-        if !centroids.is_empty() {
-            for i in 0..centroids.len()-1{
-                let  (mut min_index,mut min_distance) = (i+1,centroids[i+1].manhattan_distance(centroids[i]) );
-                for j in i+1..centroids.len(){
-                    let this_distance = centroids[j].manhattan_distance(centroids[i]);
-                    if this_distance  <= min_distance{
-                        min_distance = this_distance;
-                        min_index = j;
+        // Content and elevation are computed per tile, one StdRng for each chunk of columns.
+        // The seeds are drawn in order from the master rng, so the thread count doesn't matter.
+        let chunk_seeds: Vec<u64> = (0..self.width.div_ceil(CHUNK_COLUMNS)).map(|_| rng.gen()).collect();
+        let (noise_bundle, elevation_factor, preworld) = (&self.noise_bundle, self.elevation_factor, &preworld);
+        let biomes = noise_bundle.get_biomes().map(|table| table.sampler(noise_bundle.get_seed()));
+        let biomes = &biomes;
+        par_columns(&pool, &mut map, |start, columns| {
+            let mut chunk_rng = StdRng::seed_from_u64(chunk_seeds[start / CHUNK_COLUMNS]);
+            for (offset, column) in columns.iter_mut().enumerate() {
+                let x = start + offset;
+                for (y, tile) in column.iter_mut().enumerate() {
+                    // With the biomes the TileType of the zone is replaced by the one of the biome
+                    let biome = biomes.as_ref().and_then(|biomes| biomes.biome(x as f64, y as f64, preworld[x][y].elevation));
+                    if let Some(biome) = biome {
+                        tile.tile_type = biome.get_tile_type();
                     }
+                    // Generate the content following the rules of the biome or of NoiseBundle.content_distribution
+                    tile.content = match biome.and_then(|biome| biome.put_content(&mut chunk_rng)) {
+                        Some(content) => content,
+                        None => noise_bundle.put_content(tile.tile_type, &mut chunk_rng),
+                    };
+                    // (e in [0.0, 1.0] * f in [0.0, elevation_factor]) as usize
+                    tile.elevation = ((preworld[x][y].elevation as f64 / 100.0) * elevation_factor) as usize;
                 }
-                centroids.swap(i+1, min_index);
-                //println!("Building road: {}", i as f64 / centroids.len() as f64 * 100.0);
-                let path = shortest_path(&map, centroids[i],centroids[i+1]);
-                match path {
-                    Ok(tiles) => {build_road(&mut map, tiles)},
-                    Err(_) => {},
-                }
-            
+            }
+        });
+
+        if let Some(clustering) = self.noise_bundle.get_content_clustering() {
+            cluster_contents(&mut map, clustering, self.noise_bundle.get_seed());
+        }
+
+        // The rivers first: the cliffs aren't raised on water, so no Wall blocks a river
+        if let Some(rivers) = &self.rivers {
+            carve_rivers(&mut map, &heightmap, rivers, &self.noise_bundle, &mut rng);
+        }
+
+        if let Some(cliffs) = &self.cliffs {
+            raise_cliffs(&mut map, &heightmap, cliffs, &mut rng);
+        }
+
+        // Avoid to connect lavapool and deepwater with the other zones of the map,
+        // and the centroids that ended up on a cliff
+        let centroids: Vec<Vector2> = zone_centroids.into_iter()
+            .filter(|c| is_spawnable(&map[c.x][c.y], &self.not_spawnable))
+            .collect();
+
+        // Road network: the minimum spanning tree of the Delaunay triangulation of the centroids,
+        // plus the shortest of the other edges to make loops. Every edge is routed with A*
+        let (tree, others) = minimum_spanning_tree(&centroids, &delaunay(&centroids));
+        let loops = (others.len() as f64 * self.road_loops).round() as usize;
+        let mut finder = self.roads.finder();
+        for (a, b) in tree.into_iter().chain(others.into_iter().take(loops)) {
+            if let Ok(tiles) = finder.find_with_rng(&map, centroids[a], centroids[b], &mut rng) {
+                // The new Street tiles get their content from the ContentDist
+                build_road(&mut map, &tiles, |_| self.noise_bundle.put_content(TileType::Street, &mut rng));
+                decorate_road(&mut map, &tiles, &self.roads);
+            }
+        }
+
+        let mut report = GenerationReport::default();
+        if let Some(settlements) = &self.settlements {
+            report.settlements = build_settlements(&mut map, settlements, &self.noise_bundle, &mut rng)
+                .into_iter()
+                .map(|center| center.as_tuple())
+                .collect();
+        }
+
+        // After the settlements, their streets are part of the network too
+        if let Some(max_step) = self.roads.get_max_step() {
+            grade_roads(&mut map, max_step);
+        }
+
+        // If nothing is spawnable the center becomes a Grass tile, the robot must spawn somewhere
+        let spawn = match pick_spawn(&map, self.spawn_strategy, &self.not_spawnable, &mut rng) {
+            Some(spawn) => spawn,
+            None => {
+                let center = Vector2::new(self.width / 2, self.height / 2);
+                map[center.x][center.y].tile_type = TileType::Grass;
+                map[center.x][center.y].content = Content::None;
+                center
+            }
+        };
+        clear_hazards(&mut map, spawn, self.spawn_safe_radius);
+
+        if let Some(teleports) = &self.teleports {
+            report.teleports = link_islands(&mut map, spawn, teleports, &mut rng)
+                .into_iter()
+                .map(|(landing, island)| (landing.as_tuple(), island.as_tuple()))
+                .collect();
+        }
+
+        // Quotas last, nothing can remove or add contents after them. They keep the Fire out of the safe radius
+        enforce_quotas(&mut map, &self.content_quotas, spawn, self.spawn_safe_radius, &mut rng)?;
+        if let Some(coverage) = &self.coverage {
+            report.coverage = coverage.measure(&map);
+        }
+        if self.check_max_quantity {
+            for tile in map.iter().flatten() {
+                check_max_quantity(&tile.content)?;
             }
         }
 
         // Return "The World"
-        (map, (0,0), self.weather_conditions.clone(), self.max_score, self.score_table.clone())
+        Ok(((map, spawn.as_tuple(), weather_conditions, self.max_score, self.score_table.clone()), report))
 
     }
 
@@ -175,50 +538,35 @@ impl WorldGenerator {
 
 impl WorldGenerator {
 
-    fn generate_preworld(&self) -> Vec<Vec<PreTile>> {
-        let scale = self.noise_bundle.get_scale();
-        let noise_fn = &self.noise_bundle.noise_fn;
-
-        let mut matrix: Vec<Vec<PreTile>> = Vec::with_capacity(self.size);
-
-        for x in 0..self.size {
-            let nx = x as f64 * scale / self.size as f64;
-            let mut row: Vec<PreTile> = Vec::with_capacity(self.size);
-            for y in 0..self.size {
-                let ny = y as f64 * scale / self.size as f64;
-                let mut elevation = noise_fn.get([nx, ny]);
-                
-                // If the noise function wasn't set up properly to generate the result with the
-                // octaves is not a problems, can be do setting NoiseBundle.octaves.
-                // If the NoiseBundle.noise_fn comprehend already the octaves just set the 
-                // NoiseBundle.octaves to 0.
-                // IMPORTANT, if you don't follow this the side effect will be:
-                // (n) x (m) octaves obtained. 
-                for i in 1..self.noise_bundle.get_octaves() as i32 {
-                    elevation += (1.0 / 2.0_f64.powi(i))
-                        * noise_fn.get([nx * 2.0_f64.powi(i) + 0.420, ny * 2.0_f64.powi(i) - 0.420]);
+    /// Continuous elevation of every tile, in [0.0, 1.0] if the noise_fn respects its bounds
+    fn generate_heightmap(&self, pool: &ThreadPool) -> Vec<Vec<f64>> {
+        let noise_bundle = &self.noise_bundle;
+
+        let mut heightmap: Vec<Vec<f64>> = vec![vec![0.0; self.height]; self.width];
+
+        // Every column is independent, the noise is sampled chunk by chunk in the threadpool
+        par_columns(pool, &mut heightmap, |start, columns| {
+            for (offset, column) in columns.iter_mut().enumerate() {
+                for (y, elevation) in column.iter_mut().enumerate() {
+                    // Same unit on both axes, a rectangular map isn't stretched
+                    *elevation = noise_bundle.sample_tile((start + offset) as f64, y as f64);
                 }
-                // Why this magic number?
-                // lim n->+inf of the series 1/(2^{n}) = 2
-                // If e > 1 the TileType is not a problem, it's implemented in the dispatcher
-                elevation /= 1.8765420;
-
-                // Normalize the elevation, users already knows that they have to
-                // use a noise_function that return values in [-1.0, 1.0] or wrap another
-                // function with a different range 
-                elevation = (elevation + 1.0) / 2.0;
-
-                // We are assuming elevation <= 1, but if this is not respected it's not a problem.
-                row.push(self.noise_bundle.noise_to_pretile((elevation * 100.0) as usize));
             }
-            matrix.push(row);
-        }
-        matrix
+        });
+        heightmap
     }
 
-    pub(crate) fn get_zones(&self, preworld: &mut Vec<Vec<PreTile>>) -> Vec<Vec<Zone>> {
+    /// Quantize the heightmap in the zones of the noise_distribution
+    fn generate_preworld(&self, heightmap: &[Vec<f64>], noise_distribution: &NoiseDistribution) -> Vec<Vec<PreTile>> {
+        heightmap.iter().map(|column| {
+            // We are assuming elevation <= 1, but if this is not respected it's not a problem.
+            column.iter().map(|elevation| noise_distribution.pretile((elevation * 100.0) as usize)).collect()
+        }).collect()
+    }
+
+    pub(crate) fn get_zones(&self, preworld: &mut [Vec<PreTile>], noise_distribution: &NoiseDistribution) -> Vec<Vec<Zone>> {
 
-        let dist_size = self.noise_bundle.get_noise_distribution().len();
+        let dist_size = noise_distribution.len();
         let mut zones: Vec<Vec<Zone>> = Vec::with_capacity(dist_size);
         for _ in 0..dist_size {
             zones.push(vec![]);
@@ -227,11 +575,8 @@ impl WorldGenerator {
         // DFS to obtain a Vector of zones with the same TileType
         for x in 0..preworld.len() {
             for y in 0..preworld[0].len() {
-                match preworld[x][y].pre_tiletype {
-                    PreTileType::Zone(i) => {
-                        zones[i].push(Zone::dfs(preworld, i, Vector2::new(x, y)))
-                    }
-                    _ => {}
+                if let PreTileType::Zone(i) = preworld[x][y].pre_tiletype {
+                    zones[i].push(Zone::dfs(preworld, i, Vector2::new(x, y)))
                 }
             }
         }
@@ -240,22 +585,41 @@ impl WorldGenerator {
 
 
     // Generate standard "weather forecast", not based on any real world dynamic.
-    fn default_weather_conditions( starting_hour:u8, time_progression:u8, number:usize) -> EnvironmentalConditions{
+    fn default_weather_conditions<R: Rng>( starting_hour:u8, time_progression:u8, number:usize, rng: &mut R) -> Result<EnvironmentalConditions, GeneratorError>{
         let weather_vec = [WeatherType::Sunny,WeatherType::Rainy,WeatherType::Foggy,WeatherType::TrentinoSnow,WeatherType::TropicalMonsoon];
         let mut weather_cycle:Vec<WeatherType> = Vec::new();
         for _ in 0..number{
-            weather_cycle.push(*weather_vec.choose(&mut thread_rng()).unwrap());
+            weather_cycle.push(*weather_vec.choose(rng).unwrap());
     
         }
-        EnvironmentalConditions::new(weather_cycle.as_slice(),starting_hour,time_progression).expect("Weird")
+        EnvironmentalConditions::new(weather_cycle.as_slice(), time_progression, starting_hour).map_err(|_| GeneratorError::InvalidWeather)
     }
 }
 
+/// Split the columns of the matrix in chunks of CHUNK_COLUMNS and run the job on each of them
+/// in the pool, job receives the x of the first column of its chunk.
+fn par_columns<T, F>(pool: &ThreadPool, matrix: &mut [Vec<T>], job: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [Vec<T>]) + Sync,
+{
+    let job = &job;
+    scope_with(pool, |scope| {
+        for (i, columns) in matrix.chunks_mut(CHUNK_COLUMNS).enumerate() {
+            scope.execute(move || job(i * CHUNK_COLUMNS, columns));
+        }
+    });
+}
+
 ////////////////////////////////////////////////////////////////////
 
 
 impl Generator for WorldGenerator {
-    fn gen(&mut self) -> robotics_lib::world::world_generator::World {
-        self.build()
+    /// Panics if the generation fails, use WorldGenerator::try_gen to get the error
+    fn gen(&mut self) -> World {
+        match self.try_gen() {
+            Ok(world) => world,
+            Err(e) => panic!("World generation failed: {:?}", e),
+        }
     }   
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use rand::{seq::SliceRandom, Rng};
+use robotics_lib::world::tile::{Tile, TileType};
+
+use crate::utils::{generator_error::GeneratorError, vector2::Vector2};
+
+use super::noise_bundle::NoiseBundle;
+
+/// Parameters of the river pass, rivers are traced downhill on the heightmap
+/// and carved as ShallowWater channels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiverConfig {
+    /// Fraction of the tiles above source_elevation that become a river source
+    source_density: f64,
+    /// Minimum elevation, in [0.0, 1.0], of a river source
+    source_elevation: f64,
+    /// Rivers shorter than this (in tiles) are discarded
+    min_length: usize,
+    /// Accumulated flow needed to widen the river by one tile
+    flow_per_width: f64,
+    /// Maximum width of a river
+    max_width: usize,
+}
+
+impl Default for RiverConfig {
+    fn default() -> Self {
+        Self {
+            source_density: 0.002,
+            source_elevation: 0.6,
+            min_length: 20,
+            flow_per_width: 400.0,
+            max_width: 4,
+        }
+    }
+}
+
+impl RiverConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_source_density(mut self, source_density: f64) -> Self {
+        self.source_density = source_density;
+        self
+    }
+
+    pub fn set_source_elevation(mut self, source_elevation: f64) -> Self {
+        self.source_elevation = source_elevation;
+        self
+    }
+
+    pub fn set_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    pub fn set_flow_per_width(mut self, flow_per_width: f64) -> Self {
+        self.flow_per_width = flow_per_width;
+        self
+    }
+
+    pub fn set_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if !(0.0..=1.0).contains(&self.source_density) {
+            return Err(GeneratorError::InvalidParameter("rivers.source_density".to_string()));
+        }
+        if !(0.0..=1.0).contains(&self.source_elevation) {
+            return Err(GeneratorError::InvalidParameter("rivers.source_elevation".to_string()));
+        }
+        if !self.flow_per_width.is_finite() || self.flow_per_width <= 0.0 {
+            return Err(GeneratorError::InvalidParameter("rivers.flow_per_width".to_string()));
+        }
+        if self.max_width == 0 {
+            return Err(GeneratorError::InvalidParameter("rivers.max_width".to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn is_water(tile_type: TileType) -> bool {
+    matches!(tile_type, TileType::DeepWater | TileType::ShallowWater)
+}
+
+/// Lowest of the 4 neighbours strictly below p, None if p is a basin
+fn downhill(heightmap: &[Vec<f64>], p: Vector2) -> Option<Vector2> {
+    p.neighbours(heightmap.len(), heightmap[0].len())
+        .into_iter()
+        .filter(|n| heightmap[n.x][n.y] < heightmap[p.x][p.y])
+        .min_by(|a, b| heightmap[a.x][a.y].total_cmp(&heightmap[b.x][b.y]))
+}
+
+/// Every tile receives one unit of rain and passes all its flow to its downhill neighbour,
+/// the highest tiles first.
+fn flow_accumulation(heightmap: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let (width, height) = (heightmap.len(), heightmap[0].len());
+    let mut order: Vec<Vector2> = (0..width).flat_map(|x| (0..height).map(move |y| Vector2::new(x, y))).collect();
+    order.sort_by(|a, b| heightmap[b.x][b.y].total_cmp(&heightmap[a.x][a.y]));
+
+    let mut flow = vec![vec![1.0; height]; width];
+    for p in order {
+        if let Some(next) = downhill(heightmap, p) {
+            flow[next.x][next.y] += flow[p.x][p.y];
+        }
+    }
+    flow
+}
+
+/// Carve the rivers on the map.
+/// A river starts from a random high tile and flows downhill until it reaches the sea,
+/// the border of the map or a basin, where it leaves a small lake.
+/// Runs before the roads: a road crossing a river paves it with a Street bridge.
+pub(crate) fn carve_rivers<R: Rng>(map: &mut [Vec<Tile>], heightmap: &[Vec<f64>], config: &RiverConfig, noise_bundle: &NoiseBundle, rng: &mut R) {
+    if map.is_empty() || map[0].is_empty() {
+        return;
+    }
+    let (width, height) = (map.len(), map[0].len());
+    let flow = flow_accumulation(heightmap);
+
+    let mut sources: Vec<Vector2> = (0..width)
+        .flat_map(|x| (0..height).map(move |y| Vector2::new(x, y)))
+        .filter(|p| heightmap[p.x][p.y] >= config.source_elevation && !is_water(map[p.x][p.y].tile_type))
+        .collect();
+    let count = (sources.len() as f64 * config.source_density).round() as usize;
+    sources.shuffle(rng);
+    sources.truncate(count);
+
+    for source in sources {
+        // Trace the river downhill
+        let mut river = vec![source];
+        let mut current = source;
+        while !is_water(map[current.x][current.y].tile_type) {
+            match downhill(heightmap, current) {
+                Some(next) => {
+                    river.push(next);
+                    current = next;
+                }
+                None => break,
+            }
+        }
+        if river.len() < config.min_length {
+            continue;
+        }
+
+        for p in river {
+            let river_width = ((1.0 + flow[p.x][p.y] / config.flow_per_width) as usize).clamp(1, config.max_width.max(1));
+            let (before, after) = ((river_width - 1) / 2, river_width / 2);
+            for column in &mut map[p.x.saturating_sub(before)..(p.x + after + 1).min(width)] {
+                for tile in &mut column[p.y.saturating_sub(before)..(p.y + after + 1).min(height)] {
+                    if !is_water(tile.tile_type) {
+                        tile.tile_type = TileType::ShallowWater;
+                        tile.content = noise_bundle.put_content(TileType::ShallowWater, rng);
+                    }
+                }
+            }
+        }
+    }
+}
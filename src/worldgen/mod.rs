@@ -1,2 +1,17 @@
 pub mod generator;
-pub  mod noise_bundle;
\ No newline at end of file
+pub  mod noise_bundle;
+pub mod spawn;
+pub(crate) mod chunk;
+pub mod hydrology;
+pub mod erosion;
+pub mod biome;
+pub mod clustering;
+pub mod quota;
+pub mod teleport;
+pub mod report;
+pub mod cliff;
+pub mod settlement;
+pub mod road;
+pub mod bounded;
+pub mod coverage;
+pub mod warp;
@@ -1,61 +1,98 @@
-use std::{cmp::max, collections::HashMap, ops::Range};
+use std::{cmp::max, collections::HashMap, ops::Range, sync::OnceLock};
 
-use noise::{NoiseFn, Perlin};
+use noise::Perlin;
 use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
 use robotics_lib::world::tile::{Content, TileType};
 
 use crate::utils::{generator_error::GeneratorError, tile::PreTile};
 
+use super::{biome::BiomeTable, bounded::Bounded, clustering::ContentClustering, warp::{DomainWarp, WarpSampler}};
+
+/// Side of the grid of tiles sampled to compute the quantiles of the noise field
+const QUANTILE_GRID: usize = 128;
+
+/// Histogram equalization by rank: the i-th lowest of n elevations becomes (i + 0.5) / n,
+/// so the fraction of tiles below any elevation e in [0.0, 1.0] is e. The ties are broken by position
+pub(crate) fn equalize(heightmap: &mut [Vec<f64>]) {
+    let height = heightmap.first().map_or(0, |column| column.len());
+    let mut order: Vec<(usize, usize)> = (0..heightmap.len()).flat_map(|x| (0..height).map(move |y| (x, y))).collect();
+    order.sort_by(|a, b| heightmap[a.0][a.1].total_cmp(&heightmap[b.0][b.1]));
+    let n = order.len() as f64;
+    for (rank, (x, y)) in order.into_iter().enumerate() {
+        heightmap[x][y] = (rank as f64 + 0.5) / n;
+    }
+}
+
 /// Bundle of the parameters to generate the Tile: Type/Content
 /// and the "map topology"
 pub  struct NoiseBundle {
-    /// IMPORTANT: the seed it's NOT used for the NoiseFunction seed, just for
-    /// the StdRng of the TileType/Content dispatcher
-    /// If you want to seed the NoiseFunction pass the initialized noise_fn
+    /// Master seed of the generation: every random choice (TileType/Content dispatcher,
+    /// road tie-breaking, weather) is drawn from a StdRng seeded with it.
+    /// The default Perlin noise_fn is seeded with it too, a custom noise_fn set
+    /// with set_noise_fn must be passed ALREADY INITIALIZED WITH ITS OWN SEED.
     seed: u32,
-    /// Pass already seeded. must implement the NoiseFn<f64, 2> Trait of the noise crate.
+    /// Pass already seeded. must implement the Bounded<f64, 2> Trait (a NoiseFn<f64, 2> of the
+    /// noise crate with known bounds), wrap it in a WithBounds if its bounds can't be derived.
+    /// Send + Sync because the map is sampled in parallel.
     /// PASS the noise function ALREADY INITIALIZED WITH THE SEED!!!
-    pub noise_fn: Box<dyn NoiseFn<f64, 2>>,
+    pub noise_fn: Box<dyn Bounded<f64, 2> + Send + Sync>,
+    /// (min, max) of the noise_fn, computed once when it's set: the fractals build their
+    /// source noises to derive them, too slow to do it for every sample
+    bounds: (f64, f64),
+    /// True once the user replaced the default Perlin, so set_seed doesn't overwrite it
+    custom_noise_fn: bool,
     /// "Zoom-out" factor.
     /// The bigger the scale the bigger the zoom out of the world,
     /// also more "Chaotic" with a bigger entropy
     scale: f64,
+    /// Tiles spanned by `scale` units of noise. The noise field depends only on the
+    /// world coordinates of the tile, not on the map size, so maps and chunks can be tiled.
+    period: f64,
     /// IMPORTANT: if the noise_fn is set up with the octaves,
     /// Set this paramer to ZERO 0. 
     octaves: usize,
     /// Noise distribution as promised
-    noise_distribution: Vec<(Range<usize>, Vec<TileType>)>,
+    noise_distribution: NoiseDistribution,
+    /// If true the elevation is histogram-equalized before the bands are applied,
+    /// a band 0..25 then covers a quarter of the tiles whatever the distribution of the noise_fn
+    equalize: bool,
+    /// Sorted sample of the elevations of the noise field, the quantiles used to equalize the chunks.
+    /// Computed on the first use, reset by the setters that change the noise field
+    quantiles: OnceLock<Vec<f64>>,
     /// Content distribution as promised
     content_distribution: ContentDist,
-    rng: StdRng,
+    /// If set, the TileType and the Content come from the biome of the tile
+    /// (elevation x moisture x temperature) instead of the noise_distribution band
+    biomes: Option<BiomeTable>,
+    /// If set, the chosen contents are grouped in clusters after the content rolls
+    content_clustering: Option<ContentClustering>,
+    /// If set, the coordinates are warped before the noise_fn is sampled
+    domain_warp: Option<DomainWarp>,
+    /// Noises of the domain_warp, seeded with the master seed
+    warp_sampler: Option<WarpSampler>,
 }
 
 impl Default for NoiseBundle{
     fn default() -> Self {
         let rastafariani_seed = thread_rng().gen::<u32>();
 
+        let noise_fn = Perlin::new(rastafariani_seed);
         Self {
             seed: rastafariani_seed,
-            noise_fn: Box::new(Perlin::new(rastafariani_seed)),
+            bounds: noise_fn.bounds(),
+            noise_fn: Box::new(noise_fn),
+            custom_noise_fn: false,
             scale: 4.20, // It's Rust time :)
+            period: 1024.0,
             octaves: 5, 
-            noise_distribution: vec![
-                (0..25, vec![TileType::DeepWater, TileType::ShallowWater]),
-                (25..35, vec![TileType::ShallowWater]),
-                (
-                    35..50,
-                    vec![TileType::Sand, TileType::Grass, TileType::Grass],
-                ),
-                (50..60, vec![TileType::Grass]),
-                (
-                    60..70,
-                    vec![TileType::Sand, TileType::Hill, TileType::Mountain],
-                ),
-                (70..81, vec![TileType::Mountain]),
-                (81..100, vec![TileType::Snow, TileType::Lava]),
-            ], 
+            noise_distribution: NoiseDistribution::default(),
+            equalize: false,
+            quantiles: OnceLock::new(),
             content_distribution: ContentDist::default(),
-            rng: StdRng::seed_from_u64(rastafariani_seed as u64),
+            biomes: None,
+            content_clustering: None,
+            domain_warp: None,
+            warp_sampler: None,
         }
     } 
 }
@@ -68,58 +105,137 @@ impl  NoiseBundle {
         Self::default()
     }
 
+    /// Set the master seed, if the noise_fn is still the default Perlin it's reseeded as well
     pub fn set_seed(mut self, seed: u32)-> Self {
         self.seed = seed;
-        self.rng = StdRng::seed_from_u64(seed as u64);
+        if !self.custom_noise_fn {
+            let noise_fn = Perlin::new(seed);
+            self.bounds = noise_fn.bounds();
+            self.noise_fn = Box::new(noise_fn);
+            self.quantiles = OnceLock::new();
+        }
+        if let Some(domain_warp) = &self.domain_warp {
+            self.warp_sampler = Some(domain_warp.sampler(seed));
+            self.quantiles = OnceLock::new();
+        }
         self
     }
 
-    pub fn set_noise_fn(mut self, noise_fn: Box<dyn NoiseFn<f64, 2>>) -> Self {
+    pub fn set_noise_fn(mut self, noise_fn: Box<dyn Bounded<f64, 2> + Send + Sync>) -> Self {
+        self.bounds = noise_fn.bounds();
         self.noise_fn = noise_fn;
+        self.custom_noise_fn = true;
+        self.quantiles = OnceLock::new();
         self
     }
 
     pub fn set_scale(mut self, scale: f64)-> Self {
         self.scale = scale;
+        self.quantiles = OnceLock::new();
+        self
+    }
+
+    pub fn set_period(mut self, period: f64)-> Self {
+        self.period = period;
+        self.quantiles = OnceLock::new();
         self
     }
 
     pub fn set_octaves(mut self, octaves: usize)-> Self {
         self.octaves = octaves;
+        self.quantiles = OnceLock::new();
         self
     }
 
-    pub fn set_noise_distribution(mut self, noise_distribution: Vec<(Range<usize>, Vec<TileType>)> )-> Self {
-        if !noise_distribution.is_empty() {
-            self.noise_distribution = noise_distribution;
-        }
+    pub fn set_noise_distribution(mut self, noise_distribution: NoiseDistribution)-> Result<Self,GeneratorError> {
+        noise_distribution.is_valid()?;
+        self.noise_distribution = noise_distribution;
+        Ok(self)
+    }
+
+    /// Equalize the elevation so that every band of the noise_distribution covers its share of the tiles.
+    /// The heightmap of WorldGenerator::gen() is equalized by rank after the erosion, exactly;
+    /// the chunks use the quantiles of a sample of the noise field, so they stay seamless.
+    /// The elevation of the tiles, the rivers and the cliffs follow the equalized heightmap.
+    pub fn set_equalize(mut self, equalize: bool)-> Self {
+        self.equalize = equalize;
         self
     }
 
     pub fn set_content_distribution(mut self, content_distribution: ContentDist)-> Result<Self,GeneratorError> {
         match content_distribution.is_valid(){
             Ok(_) => {self.content_distribution = content_distribution; Ok(self)}
-            Err(e) => {return Err(e)}
+            Err(e) => Err(e)
+        }
+
+    }
+
+
+    pub fn set_biomes(mut self, biomes: Option<BiomeTable>)-> Result<Self,GeneratorError> {
+        if let Some(table) = &biomes {
+            table.is_valid()?;
         }
+        self.biomes = biomes;
+        Ok(self)
+    }
 
+    pub fn set_content_clustering(mut self, content_clustering: Option<ContentClustering>)-> Self {
+        self.content_clustering = content_clustering;
+        self
+    }
+
+    pub fn set_domain_warp(mut self, domain_warp: Option<DomainWarp>)-> Result<Self,GeneratorError> {
+        if let Some(warp) = &domain_warp {
+            warp.is_valid()?;
+        }
+        self.warp_sampler = domain_warp.as_ref().map(|warp| warp.sampler(self.seed));
+        self.domain_warp = domain_warp;
+        self.quantiles = OnceLock::new();
+        Ok(self)
     }
 
+    pub (crate) fn get_domain_warp(&self)-> Option<&DomainWarp> {
+        self.domain_warp.as_ref()
+    }
+
+    pub (crate) fn get_content_clustering(&self)-> Option<&ContentClustering> {
+        self.content_clustering.as_ref()
+    }
+
+    pub (crate) fn get_content_distribution(&self)-> &ContentDist {
+        &self.content_distribution
+    }
+
+    pub (crate) fn get_biomes(&self)-> Option<&BiomeTable> {
+        self.biomes.as_ref()
+    }
 
     pub (crate) fn get_seed(&self)-> u32 {
         self.seed
     }
 
-    pub (crate) fn get_scale(&self)-> f64 {
-        self.scale
+    /// Fresh StdRng seeded with the master seed, every generation starts from the same state
+    pub (crate) fn rng(&self)-> StdRng {
+        StdRng::seed_from_u64(self.seed as u64)
     }
 
+    #[cfg(test)]
     pub (crate) fn get_octaves(&self)-> usize {
         self.octaves
     }
 
-    pub (crate) fn get_noise_distribution(&self)-> Vec<(Range<usize>, Vec<TileType>)> {
-        self.noise_distribution.clone()
-}
+    /// (min, max) of the noise_fn
+    pub (crate) fn get_bounds(&self)-> (f64, f64) {
+        self.bounds
+    }
+
+    pub (crate) fn get_noise_distribution(&self)-> &NoiseDistribution {
+        &self.noise_distribution
+    }
+
+    pub (crate) fn is_equalized(&self)-> bool {
+        self.equalize
+    }
 
 
 }
@@ -127,36 +243,204 @@ impl  NoiseBundle {
 ////////////////////////////////////////////////////////////////////
 
 impl NoiseBundle {
+    /// Elevation of the point, remapped exactly from the bounds of the octave sum onto [0.0, 1.0]
+    pub(crate) fn sample(&self, nx: f64, ny: f64) -> f64 {
+        // Every octave is sampled around the warped point
+        let [nx, ny] = match &self.warp_sampler {
+            Some(warp_sampler) => warp_sampler.warp([nx, ny]),
+            None => [nx, ny],
+        };
+        let mut elevation = self.noise_fn.get([nx, ny]);
+        let mut amplitudes = 1.0;
+
+        // If the noise function wasn't set up properly to generate the result with the
+        // octaves is not a problems, can be do setting NoiseBundle.octaves.
+        // If the NoiseBundle.noise_fn comprehend already the octaves just set the 
+        // NoiseBundle.octaves to 0.
+        // IMPORTANT, if you don't follow this the side effect will be:
+        // (n) x (m) octaves obtained. 
+        for i in 1..self.octaves as i32 {
+            let amplitude = 1.0 / 2.0_f64.powi(i);
+            elevation += amplitude
+                * self.noise_fn.get([nx * 2.0_f64.powi(i) + 0.420, ny * 2.0_f64.powi(i) - 0.420]);
+            amplitudes += amplitude;
+        }
+
+        // Every octave is in [min, max], the sum is in [min, max] * the sum of the amplitudes
+        let (min, max) = self.bounds;
+        let (min, max) = (min * amplitudes, max * amplitudes);
+        if max > min {
+            (elevation - min) / (max - min)
+        } else {
+            0.5
+        }
+    }
+
+    /// Elevation of the tile at the world coordinates (x, y)
+    pub(crate) fn sample_tile(&self, x: f64, y: f64) -> f64 {
+        let unit = self.scale / self.period;
+        self.sample(x * unit, y * unit)
+    }
+
+    /// Elevation of the tile at the world coordinates (x, y), equalized with the quantiles
+    /// of the noise field if equalize is set. Depends only on the coordinates, used by the chunks
+    pub(crate) fn equalized_tile(&self, x: f64, y: f64) -> f64 {
+        let elevation = self.sample_tile(x, y);
+        if !self.equalize {
+            return elevation;
+        }
+        let quantiles = self.quantiles.get_or_init(|| {
+            // QUANTILE_GRID² tiles evenly spread over 4 periods on both axes
+            let step = 4.0 * self.period / QUANTILE_GRID as f64;
+            let mut sample: Vec<f64> = (0..QUANTILE_GRID)
+                .flat_map(|x| (0..QUANTILE_GRID).map(move |y| (x as f64 * step, y as f64 * step)))
+                .map(|(x, y)| self.sample_tile(x, y))
+                .collect();
+            sample.sort_by(f64::total_cmp);
+            sample
+        });
+        // Fraction of the sample below the elevation, interpolated between the two closest quantiles
+        let i = quantiles.partition_point(|q| *q < elevation);
+        if i == 0 {
+            return 0.0;
+        }
+        if i == quantiles.len() {
+            return 1.0;
+        }
+        let (low, high) = (quantiles[i - 1], quantiles[i]);
+        let fraction = if high > low { (elevation - low) / (high - low) } else { 0.0 };
+        (i as f64 - 0.5 + fraction) / quantiles.len() as f64
+    }
+
     /// Noise -> Zone(id), Zone(id) -> TileType
-    /// This function function does the id -> TileType according to the noise_distribution
+    /// This function compute the Noise -> Zone(id)
+    pub(crate) fn noise_to_pretile(&self, z: usize) -> PreTile {
+        self.noise_distribution.pretile(z)
+    }
+
+    pub(crate) fn put_content<R: Rng>(&self, ttype: TileType, rng: &mut R) -> Content {
+        self.content_distribution.get_content(ttype, rng.gen_range(0..=100))
+    }
+}
+
+/// Elevation bands, in [0, 100], and the TileTypes a zone of the band can get
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseDistribution {
+    pub(crate) bands: Vec<(Range<usize>, Vec<TileType>)>
+}
+
+impl NoiseDistribution {
+    pub fn new(bands: Vec<(Range<usize>, Vec<TileType>)>) -> Self {
+        NoiseDistribution { bands }
+    }
+
+    pub fn builder() -> NoiseDistributionBuilder {
+        NoiseDistributionBuilder { bands: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bands.is_empty()
+    }
+
+    /// Noise -> Zone(id): the band containing z, the last one if z is above all of them
+    pub(crate) fn pretile(&self, z: usize) -> PreTile {
+        assert!(!self.bands.is_empty());
+        for (counter, (range, _)) in self.bands.iter().enumerate() {
+            if range.contains(&z) {
+                return PreTile::new(counter, z);
+            }
+        }
+        PreTile::new(max::<usize>(self.bands.len(), 1) - 1, z)
+    }
+
+    /// Zone(id) -> TileType, one of the TileTypes of the band.
     /// If something goes wrong just returns ShallowWater.
-    pub(crate) fn zone_to_tiletype_dispatcher(&self, id: usize) -> TileType {
-        let (_, dist) = self.noise_distribution[id].clone();
+    pub(crate) fn zone_to_tiletype_dispatcher<R: Rng>(&self, id: usize, rng: &mut R) -> TileType {
+        let dist = &self.bands[id].1;
         if dist.len() == 1 {
             return dist[0];
         }
-        match dist.choose(&mut thread_rng()){
+        match dist.choose(rng){
             Some(tile_type) => *tile_type,
             None => TileType::ShallowWater,
         }
     }
 
-    /// Noise -> Zone(id), Zone(id) -> TileType
-    /// This function compute the Noise -> Zone(id)
-    pub(crate) fn noise_to_pretile(&self, z: usize) -> PreTile {
-        assert!(!self.noise_distribution.is_empty());
-        let mut counter: usize = 0;
-        for (range, _) in self.noise_distribution.iter() {
-            if range.contains(&z) {
-                return PreTile::new(counter, z);
+    /*
+    checks these conditions:
+    - the distribution has at least one band
+    - every band has at least one TileType, no Wall and no active teleports
+    - the bands are in order and cover all values from 0 to 100, without gaps or overlaps
+    */
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if self.bands.is_empty() {
+            return Err(GeneratorError::EmptyNoiseDistribution);
+        }
+        let mut covered: usize = 0;
+        for (range, tile_types) in self.bands.iter() {
+            if tile_types.is_empty() {
+                return Err(GeneratorError::EmptyNoiseBand);
+            }
+            if tile_types.contains(&TileType::Teleport(true)) {
+                return Err(GeneratorError::ActiveTeleport);
+            }
+            if tile_types.contains(&TileType::Wall) {
+                return Err(GeneratorError::WallInNoiseDistribution);
+            }
+            if range.start < covered || range.end <= range.start {
+                return Err(GeneratorError::OverlappingDistribution);
             }
-            counter += 1;
+            if range.start > covered {
+                return Err(GeneratorError::NoiseDistributionGap);
+            }
+            covered = range.end;
+        }
+        if covered != 101 {
+            return Err(GeneratorError::NoiseDistributionGap);
         }
-        PreTile::new(max::<usize>(self.noise_distribution.len(), 1) - 1, z)
+        Ok(())
     }
+}
 
-    pub(crate) fn put_content(&self, ttype: TileType) -> Content {
-        self.content_distribution.get_content(ttype, rand::thread_rng().gen_range(0..=100))
+impl Default for NoiseDistribution {
+    fn default() -> Self {
+        Self::new(vec![
+            (0..25, vec![TileType::DeepWater, TileType::ShallowWater]),
+            (25..35, vec![TileType::ShallowWater]),
+            (
+                35..50,
+                vec![TileType::Sand, TileType::Grass, TileType::Grass],
+            ),
+            (50..60, vec![TileType::Grass]),
+            (
+                60..70,
+                vec![TileType::Sand, TileType::Hill, TileType::Mountain],
+            ),
+            (70..81, vec![TileType::Mountain]),
+            (81..101, vec![TileType::Snow, TileType::Lava]),
+        ])
+    }
+}
+
+/// Builds a NoiseDistribution band by band, build() validates it
+pub struct NoiseDistributionBuilder {
+    bands: Vec<(Range<usize>, Vec<TileType>)>
+}
+
+impl NoiseDistributionBuilder {
+    pub fn band(mut self, range: Range<usize>, tile_types: Vec<TileType>) -> Self {
+        self.bands.push((range, tile_types));
+        self
+    }
+
+    pub fn build(self) -> Result<NoiseDistribution, GeneratorError> {
+        let distribution = NoiseDistribution::new(self.bands);
+        distribution.is_valid()?;
+        Ok(distribution)
     }
 }
 
@@ -188,7 +472,6 @@ impl ContentDist {
     pub fn is_valid(&self) -> Result<(), GeneratorError> {
         //check if the map contains all possible tiletypes
         if self.dist.len() != 11 {
-            println!("Map doesn't contain all tiletypes");
             return Err(GeneratorError::NonExhaustiveContentDistribution);
         };
         //check if there's an activated teleport
@@ -198,36 +481,42 @@ impl ContentDist {
         };
         //check if ranges overlap and do not cover from 0 to 100
         for (tile, map) in self.dist.iter() {
-            if map.is_empty() {
-                println!("Map is empty");
-                return Err(GeneratorError::NonExhaustiveContentDistribution);
-            };
-            let mut covering_range: Range<usize> = 0..0;
-            let tile_properties = tile.properties();
-            for (range, content) in map.iter() {
-                //check if tile can hold
-                if !tile_properties.can_hold(content) {
-                    return Err(GeneratorError::InvalidContent(*tile,content.clone()));
+            ContentDist::is_valid_entry(tile, map)?;
+        }
 
-                }
-                if covering_range.start == covering_range.end {
-                    covering_range = range.clone();
-                    continue;
-                }
-                if covering_range.start >= range.start || covering_range.end != range.start {
-                    return Err(GeneratorError::OverlappingDistribution);
-                }
+        Ok(())
+    }
 
-                //checks if range overlaps and if it's not in order
+    /// Checks a single TileType entry of the distribution:
+    /// - values from 0 to 100 are covered, in order and without overlaps
+    /// - TileType and content must be compatible
+    pub(crate) fn is_valid_entry(tile: &TileType, map: &[(Range<usize>, Content)]) -> Result<(), GeneratorError> {
+        if map.is_empty() {
+            return Err(GeneratorError::NonExhaustiveContentDistribution);
+        };
+        let mut covering_range: Range<usize> = 0..0;
+        let tile_properties = tile.properties();
+        for (range, content) in map.iter() {
+            //check if tile can hold
+            if !tile_properties.can_hold(content) {
+                return Err(GeneratorError::InvalidContent(*tile,content.clone()));
 
-                covering_range = covering_range.start..range.end
             }
-            if !(covering_range.start == 0 && covering_range.end == 101) {
-                println!("covering range is: {:?}",covering_range);
-                return Err(GeneratorError::NonExhaustiveContentDistribution);
-            };
-        }
+            if covering_range.start == covering_range.end {
+                covering_range = range.clone();
+                continue;
+            }
+            if covering_range.start >= range.start || covering_range.end != range.start {
+                return Err(GeneratorError::OverlappingDistribution);
+            }
+
+            //checks if range overlaps and if it's not in order
 
+            covering_range = covering_range.start..range.end
+        }
+        if !(covering_range.start == 0 && covering_range.end == 101) {
+            return Err(GeneratorError::NonExhaustiveContentDistribution);
+        };
         Ok(())
     }
 }
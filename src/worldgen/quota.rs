@@ -0,0 +1,87 @@
+use rand::{seq::SliceRandom, Rng};
+use robotics_lib::world::tile::{Content, Tile};
+
+use crate::utils::{generator_error::GeneratorError, vector2::Vector2};
+
+/// How many tiles of the World must hold a content (of any quantity)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentQuota {
+    Exactly(usize),
+    AtLeast(usize),
+    AtMost(usize),
+    /// Inclusive bounds
+    Between(usize, usize),
+}
+
+impl ContentQuota {
+    pub(crate) fn min(&self) -> usize {
+        match self {
+            ContentQuota::Exactly(n) | ContentQuota::AtLeast(n) | ContentQuota::Between(n, _) => *n,
+            ContentQuota::AtMost(_) => 0,
+        }
+    }
+
+    pub(crate) fn max(&self) -> usize {
+        match self {
+            ContentQuota::Exactly(n) | ContentQuota::AtMost(n) | ContentQuota::Between(_, n) => *n,
+            ContentQuota::AtLeast(_) => usize::MAX,
+        }
+    }
+}
+
+/// Checks the quantity of the content against the maximum of robotics_lib,
+/// for the ranges (Bin, Crate, Bank) the biggest value of the range is checked
+pub(crate) fn check_max_quantity(content: &Content) -> Result<(), GeneratorError> {
+    let max = content.properties().max();
+    let quantity = match content.get_value() {
+        (Some(value), _) => value,
+        (None, Some(range)) => range.end.saturating_sub(1),
+        (None, None) => return Ok(()),
+    };
+    if quantity > max {
+        return Err(GeneratorError::MaxContent(content.clone()));
+    }
+    Ok(())
+}
+
+/// Removes or adds contents until every quota is met.
+/// The extra contents are removed at random, the missing ones are placed at random
+/// on the empty tiles that can hold them. No Fire is placed in the square of side
+/// 2*safe_radius+1 around the spawn, the one cleared by clear_hazards.
+pub(crate) fn enforce_quotas<R: Rng>(map: &mut [Vec<Tile>], quotas: &[(Content, ContentQuota)], spawn: Vector2, safe_radius: usize, rng: &mut R) -> Result<(), GeneratorError> {
+    let safe = |x: usize, y: usize| x.abs_diff(spawn.x) <= safe_radius && y.abs_diff(spawn.y) <= safe_radius;
+    for (content, quota) in quotas {
+        check_max_quantity(content)?;
+        let kind = content.to_default();
+        let hazard = kind == Content::Fire;
+
+        let mut placed = Vec::<Vector2>::new();
+        let mut empty = Vec::<Vector2>::new();
+        for (x, column) in map.iter().enumerate() {
+            for (y, tile) in column.iter().enumerate() {
+                if tile.content.to_default() == kind {
+                    placed.push(Vector2::new(x, y));
+                } else if tile.content == Content::None && tile.tile_type.properties().can_hold(content) && !(hazard && safe(x, y)) {
+                    empty.push(Vector2::new(x, y));
+                }
+            }
+        }
+
+        if placed.len() > quota.max() {
+            placed.shuffle(rng);
+            for p in &placed[quota.max()..] {
+                map[p.x][p.y].content = Content::None;
+            }
+        } else if placed.len() < quota.min() {
+            let missing = quota.min() - placed.len();
+            if empty.len() < missing {
+                return Err(GeneratorError::UnsatisfiableQuota(content.clone()));
+            }
+            empty.shuffle(rng);
+            for p in &empty[..missing] {
+                map[p.x][p.y].content = content.clone();
+            }
+        }
+    }
+    Ok(())
+}
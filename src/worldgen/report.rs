@@ -0,0 +1,29 @@
+use super::coverage::Coverage;
+
+/// Two linked Teleport(false) tiles, (main landmass, island)
+pub type TeleportPair = ((usize, usize), (usize, usize));
+
+/// What the last generation of a WorldGenerator did, beside the World itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationReport {
+    /// The teleporters placed by link_islands
+    pub(crate) teleports: Vec<TeleportPair>,
+    /// Centers of the settlements
+    pub(crate) settlements: Vec<(usize, usize)>,
+    /// Requested and actual coverage of the groups of the CoverageTargets, empty without them
+    pub(crate) coverage: Vec<Coverage>,
+}
+
+impl GenerationReport {
+    pub fn get_teleports(&self) -> &[TeleportPair] {
+        &self.teleports
+    }
+
+    pub fn get_settlements(&self) -> &[(usize, usize)] {
+        &self.settlements
+    }
+
+    pub fn get_coverage(&self) -> &[Coverage] {
+        &self.coverage
+    }
+}
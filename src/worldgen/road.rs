@@ -0,0 +1,138 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::{generator_error::GeneratorError, pathfinding::PathFinder, vector2::Vector2};
+
+/// How the roads are routed and finished.
+/// The elevations are the ones of the Tiles, in [0, elevation_factor].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RoadConfig {
+    /// Extra cost of climbing (or descending) one unit of elevation, 0 to ignore the slopes
+    slope_penalty: u32,
+    /// Penalize the steep steps quadratically, the roads climb in switchbacks
+    switchbacks: bool,
+    /// After the roads are built their elevation is graded so that two adjacent
+    /// Street tiles differ by at most max_step, None to keep the terrain
+    max_step: Option<usize>,
+    /// (content, n): the content is put on every n-th tile of every road, if the Street can hold it
+    decorations: Vec<(Content, usize)>,
+    /// The roads are routed with A* and the Manhattan heuristic, but a Street costs 0: once the
+    /// first roads are built the heuristic overestimates and a new road may not take the cheapest
+    /// way to the network. true routes them with Dijkstra, the cheapest roads at a slower search
+    exact: bool,
+}
+
+impl RoadConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_slope_penalty(mut self, slope_penalty: u32) -> Self {
+        self.slope_penalty = slope_penalty;
+        self
+    }
+
+    pub fn set_switchbacks(mut self, switchbacks: bool) -> Self {
+        self.switchbacks = switchbacks;
+        self
+    }
+
+    pub fn set_max_step(mut self, max_step: Option<usize>) -> Self {
+        self.max_step = max_step;
+        self
+    }
+
+    pub fn set_exact(mut self, exact: bool) -> Self {
+        self.exact = exact;
+        self
+    }
+
+    /// Put the content every n tiles along the roads, like a Bin every 20 tiles
+    pub fn add_decoration(mut self, content: Content, every: usize) -> Self {
+        self.decorations.push((content, every));
+        self
+    }
+
+    /// Every decoration must have a period of at least 1 tile
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if self.decorations.iter().any(|(_, every)| *every == 0) {
+            return Err(GeneratorError::InvalidParameter("roads.decorations".to_string()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_max_step(&self) -> Option<usize> {
+        self.max_step
+    }
+
+    /// PathFinder routing the roads
+    pub(crate) fn finder(&self) -> PathFinder {
+        PathFinder::new()
+            .set_slope_penalty(self.slope_penalty)
+            .set_slope_exponent(if self.switchbacks { 2 } else { 1 })
+            .set_heuristic_scale(if self.exact { 0 } else { 1 })
+    }
+}
+
+/// Put the decorations along a road, the path goes from its start to its end
+pub(crate) fn decorate_road(map: &mut [Vec<Tile>], path: &[Vector2], roads: &RoadConfig) {
+    // gen_chunk doesn't validate the configuration
+    for (content, every) in roads.decorations.iter().filter(|(_, every)| *every > 0) {
+        for p in path.iter().skip(every - 1).step_by(*every) {
+            let tile = &mut map[p.x][p.y];
+            if tile.tile_type.properties().can_hold(content) {
+                tile.content = content.clone();
+            }
+        }
+    }
+}
+
+/// Grade the elevation of the Street tiles: afterwards two 4-adjacent Street tiles differ by at most max_step.
+/// u is the lowest grade above the terrain (fill) and l the highest one below it (cut),
+/// both computed over the road network with a Dijkstra-like propagation:
+/// u[i] = max(e[i], u[j] - max_step) and l[i] = min(e[i], l[j] + max_step) for the adjacent j.
+/// The new elevation is their mean, half cut and half fill.
+pub(crate) fn grade_roads(map: &mut [Vec<Tile>], max_step: usize) {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let is_road = |map: &[Vec<Tile>], p: Vector2| map[p.x][p.y].tile_type == TileType::Street;
+    let roads: Vec<Vector2> = (0..width)
+        .flat_map(|x| (0..height).map(move |y| Vector2::new(x, y)))
+        .filter(|p| is_road(map, *p))
+        .collect();
+
+    // Upper envelope, the highest tiles first
+    let mut upper: Vec<Vec<usize>> = map.iter().map(|column| column.iter().map(|tile| tile.elevation).collect()).collect();
+    let mut heap: BinaryHeap<(usize, usize, usize)> = roads.iter().map(|p| (upper[p.x][p.y], p.x, p.y)).collect();
+    while let Some((elevation, x, y)) = heap.pop() {
+        if elevation < upper[x][y] {
+            continue;
+        }
+        for n in Vector2::new(x, y).neighbours(width, height) {
+            if is_road(map, n) && upper[n.x][n.y] + max_step < elevation {
+                upper[n.x][n.y] = elevation - max_step;
+                heap.push((upper[n.x][n.y], n.x, n.y));
+            }
+        }
+    }
+
+    // Lower envelope, the lowest tiles first
+    let mut lower: Vec<Vec<usize>> = map.iter().map(|column| column.iter().map(|tile| tile.elevation).collect()).collect();
+    let mut heap: BinaryHeap<Reverse<(usize, usize, usize)>> = roads.iter().map(|p| Reverse((lower[p.x][p.y], p.x, p.y))).collect();
+    while let Some(Reverse((elevation, x, y))) = heap.pop() {
+        if elevation > lower[x][y] {
+            continue;
+        }
+        for n in Vector2::new(x, y).neighbours(width, height) {
+            if is_road(map, n) && lower[n.x][n.y] > elevation + max_step {
+                lower[n.x][n.y] = elevation + max_step;
+                heap.push(Reverse((lower[n.x][n.y], n.x, n.y)));
+            }
+        }
+    }
+
+    for p in roads {
+        map[p.x][p.y].elevation = (upper[p.x][p.y] + lower[p.x][p.y]) / 2;
+    }
+}
@@ -0,0 +1,204 @@
+use std::ops::Range;
+
+use rand::{seq::SliceRandom, Rng};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::{generator_error::GeneratorError, vector2::Vector2};
+
+use super::noise_bundle::{ContentDist, NoiseBundle};
+
+/// Parameters of the settlement pass: small towns with a street grid,
+/// built on the road junctions or on flat land near the water.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettlementConfig {
+    /// Maximum number of settlements, fewer are built if there aren't enough good spots
+    count: usize,
+    /// Half side of the square of a settlement, (2 * size + 1) tiles per side
+    size: usize,
+    /// Distance between two parallel streets of the grid
+    block: usize,
+    /// Contents of the lots facing the streets: every lot rolls a value in [0, 100]
+    /// and gets the content of the range containing it, the ranges cover 0..101
+    contents: Vec<(Range<usize>, Content)>,
+}
+
+impl Default for SettlementConfig {
+    fn default() -> Self {
+        Self {
+            count: 4,
+            size: 6,
+            block: 4,
+            contents: vec![
+                (0..50, Content::Building),
+                (50..62, Content::Market(5)),
+                (62..72, Content::Bank(0..50)),
+                (72..92, Content::Bin(0..5)),
+                (92..101, Content::None),
+            ],
+        }
+    }
+}
+
+impl SettlementConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    pub fn set_size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn set_block(mut self, block: usize) -> Self {
+        self.block = block;
+        self
+    }
+
+    pub fn set_contents(mut self, contents: Vec<(Range<usize>, Content)>) -> Self {
+        self.contents = contents;
+        self
+    }
+
+    /// The size must be at least 1, the block at least 2 (or there are only streets)
+    /// and the contents must be valid on Grass
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if self.size == 0 {
+            return Err(GeneratorError::InvalidParameter("settlements.size".to_string()));
+        }
+        if self.block < 2 {
+            return Err(GeneratorError::InvalidParameter("settlements.block".to_string()));
+        }
+        ContentDist::is_valid_entry(&TileType::Grass, &self.contents)
+    }
+
+    fn put_content<R: Rng>(&self, rng: &mut R) -> Content {
+        let value = rng.gen_range(0..=100);
+        self.contents.iter()
+            .find(|(range, _)| range.contains(&value))
+            .map_or(Content::None, |(_, content)| content.clone())
+    }
+}
+
+fn is_buildable(tile_type: TileType) -> bool {
+    matches!(tile_type, TileType::Grass | TileType::Sand | TileType::Hill | TileType::Street)
+}
+
+fn is_water(tile_type: TileType) -> bool {
+    matches!(tile_type, TileType::DeepWater | TileType::ShallowWater)
+}
+
+/// Summed-area table of a predicate over the map, counts the matching tiles of a rectangle in O(1)
+struct AreaCount {
+    sums: Vec<Vec<u32>>,
+}
+
+impl AreaCount {
+    fn new<F: Fn(&Tile) -> bool>(map: &[Vec<Tile>], predicate: F) -> Self {
+        let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+        let mut sums = vec![vec![0; height + 1]; width + 1];
+        for x in 0..width {
+            for y in 0..height {
+                sums[x + 1][y + 1] = predicate(&map[x][y]) as u32 + sums[x][y + 1] + sums[x + 1][y] - sums[x][y];
+            }
+        }
+        Self { sums }
+    }
+
+    /// Matching tiles in [x0, x1) x [y0, y1)
+    fn count(&self, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) -> u32 {
+        self.sums[x1][y1] + self.sums[x0][y0] - self.sums[x0][y1] - self.sums[x1][y0]
+    }
+}
+
+/// Build the settlements, returns their centers.
+///
+/// A spot is good if at least 80% of the square of the settlement is buildable land;
+/// the road junctions (Streets with 3 or more Street neighbours) come first, then
+/// the Grass tiles with some water in the square. The settlements never overlap.
+/// Every settlement gets a street grid centered on the spot: the new Street tiles get the Street
+/// contents of the ContentDist, the roads already there keep theirs. The tiles along the
+/// streets get the contents of the config, the one closest to the center is a Market
+/// if the config has one.
+pub(crate) fn build_settlements<R: Rng>(map: &mut [Vec<Tile>], config: &SettlementConfig, noise_bundle: &NoiseBundle, rng: &mut R) -> Vec<Vector2> {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let size = config.size;
+    if width < 2 * size + 1 || height < 2 * size + 1 {
+        return Vec::new();
+    }
+    let buildable = AreaCount::new(map, |tile| is_buildable(tile.tile_type));
+    let water = AreaCount::new(map, |tile| is_water(tile.tile_type));
+    let area = ((2 * size + 1) * (2 * size + 1)) as u32;
+
+    // (priority, spot), the squares fully inside the map only
+    let mut candidates = Vec::<(u8, Vector2)>::new();
+    for x in size..width - size {
+        for y in size..height - size {
+            let square = ((x - size, y - size), (x + size + 1, y + size + 1));
+            if buildable.count(square.0, square.1) * 5 < area * 4 {
+                continue;
+            }
+            let p = Vector2::new(x, y);
+            let junction = map[x][y].tile_type == TileType::Street
+                && p.neighbours(width, height).iter().filter(|n| map[n.x][n.y].tile_type == TileType::Street).count() >= 3;
+            if junction {
+                candidates.push((2, p));
+            } else if map[x][y].tile_type == TileType::Grass && water.count(square.0, square.1) > 0 {
+                candidates.push((1, p));
+            }
+        }
+    }
+    candidates.shuffle(rng);
+    candidates.sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+
+    let mut centers = Vec::<Vector2>::new();
+    for (_, p) in candidates {
+        if centers.len() >= config.count {
+            break;
+        }
+        if centers.iter().any(|c| c.x.abs_diff(p.x).max(c.y.abs_diff(p.y)) <= 2 * size + 1) {
+            continue;
+        }
+        lay_out(map, p, config, noise_bundle, rng);
+        centers.push(p);
+    }
+    centers
+}
+
+/// Street grid and lots of the settlement centered on center
+fn lay_out<R: Rng>(map: &mut [Vec<Tile>], center: Vector2, config: &SettlementConfig, noise_bundle: &NoiseBundle, rng: &mut R) {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let size = config.size;
+    let square = || (center.x - size..=center.x + size).flat_map(|x| (center.y - size..=center.y + size).map(move |y| Vector2::new(x, y)));
+
+    for p in square() {
+        let on_grid = p.x.abs_diff(center.x) % config.block == 0 || p.y.abs_diff(center.y) % config.block == 0;
+        // The roads crossing the settlement keep their contents, the new streets get the ones of the ContentDist
+        if on_grid && is_buildable(map[p.x][p.y].tile_type) && map[p.x][p.y].tile_type != TileType::Street {
+            map[p.x][p.y].tile_type = TileType::Street;
+            map[p.x][p.y].content = noise_bundle.put_content(TileType::Street, rng);
+        }
+    }
+
+    // Lots: buildable tiles of the settlement facing a street, closest to the center first
+    let mut lots: Vec<Vector2> = square()
+        .filter(|p| is_buildable(map[p.x][p.y].tile_type) && map[p.x][p.y].tile_type != TileType::Street)
+        .filter(|p| p.neighbours(width, height).iter().any(|n| map[n.x][n.y].tile_type == TileType::Street))
+        .collect();
+    lots.sort_by_key(|p| p.manhattan_distance(center));
+
+    let market = config.contents.iter().map(|(_, content)| content).find(|content| matches!(content, Content::Market(_)));
+    for (i, p) in lots.into_iter().enumerate() {
+        let content = match market {
+            Some(market) if i == 0 => market.clone(),
+            _ => config.put_content(rng),
+        };
+        if map[p.x][p.y].tile_type.properties().can_hold(&content) {
+            map[p.x][p.y].content = content;
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::vector2::Vector2;
+
+/// How WorldGenerator picks the robot spawn once the map is built
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpawnStrategy {
+    /// Any spawnable tile of the map
+    Random,
+    /// The spawnable tile closest to the center of the map
+    Center,
+    /// A Street tile, if the map has no roads falls back to Random
+    Road,
+    /// A tile inside the biggest connected zone of spawnable tiles
+    #[default]
+    LargestLandZone,
+}
+
+/// A tile is spawnable if its TileType is not in not_spawnable.
+/// Walls are never spawnable, the robot can't stand on them.
+pub(crate) fn is_spawnable(tile: &Tile, not_spawnable: &[TileType]) -> bool {
+    tile.tile_type != TileType::Wall && !not_spawnable.contains(&tile.tile_type)
+}
+
+/// Pick the spawn following the strategy, None if there isn't any spawnable tile
+pub(crate) fn pick_spawn<R: Rng>(map: &[Vec<Tile>], strategy: SpawnStrategy, not_spawnable: &[TileType], rng: &mut R) -> Option<Vector2> {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let spawnable = |p: &Vector2| is_spawnable(&map[p.x][p.y], not_spawnable);
+    let all = (0..width).flat_map(|x| (0..height).map(move |y| Vector2::new(x, y)));
+
+    match strategy {
+        SpawnStrategy::Random => {
+            let candidates: Vec<Vector2> = all.filter(spawnable).collect();
+            candidates.choose(rng).copied()
+        }
+        SpawnStrategy::Center => {
+            let center = Vector2::new(width / 2, height / 2);
+            all.filter(spawnable).min_by_key(|p| p.manhattan_distance(center))
+        }
+        SpawnStrategy::Road => {
+            let candidates: Vec<Vector2> = all
+                .filter(|p| map[p.x][p.y].tile_type == TileType::Street && spawnable(p))
+                .collect();
+            match candidates.choose(rng) {
+                Some(p) => Some(*p),
+                None => pick_spawn(map, SpawnStrategy::Random, not_spawnable, rng),
+            }
+        }
+        SpawnStrategy::LargestLandZone => {
+            let zone = largest_zone(map, not_spawnable);
+            zone.choose(rng).copied()
+        }
+    }
+}
+
+/// BFS over the spawnable tiles, returns the biggest 4-connected component
+fn largest_zone(map: &[Vec<Tile>], not_spawnable: &[TileType]) -> Vec<Vector2> {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let mut visited = vec![vec![false; height]; width];
+    let mut largest = Vec::<Vector2>::new();
+
+    for x in 0..width {
+        for y in 0..height {
+            if visited[x][y] || !is_spawnable(&map[x][y], not_spawnable) {
+                continue;
+            }
+            let mut zone = Vec::<Vector2>::new();
+            let mut queue = VecDeque::from([Vector2::new(x, y)]);
+            visited[x][y] = true;
+            while let Some(p) = queue.pop_front() {
+                zone.push(p);
+                for n in p.neighbours(width, height) {
+                    if !visited[n.x][n.y] && is_spawnable(&map[n.x][n.y], not_spawnable) {
+                        visited[n.x][n.y] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+            if zone.len() > largest.len() {
+                largest = zone;
+            }
+        }
+    }
+    largest
+}
+
+/// Remove the hazards (Lava tiles and Fire) in the square of side 2*radius+1 around the spawn.
+/// Lava becomes Grass, so the robot doesn't burn at the first step.
+pub(crate) fn clear_hazards(map: &mut [Vec<Tile>], spawn: Vector2, radius: usize) {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    for column in &mut map[spawn.x.saturating_sub(radius)..(spawn.x + radius + 1).min(width)] {
+        for tile in &mut column[spawn.y.saturating_sub(radius)..(spawn.y + radius + 1).min(height)] {
+            if tile.tile_type == TileType::Lava {
+                tile.tile_type = TileType::Grass;
+                tile.content = Content::None;
+            }
+            if tile.content == Content::Fire {
+                tile.content = Content::None;
+            }
+        }
+    }
+}
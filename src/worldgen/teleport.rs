@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng};
+use robotics_lib::world::tile::{Content, Tile, TileType};
+
+use crate::utils::{generator_error::GeneratorError, vector2::Vector2};
+
+/// Parameters of the teleporter pass, which links the islands the robot can't
+/// walk to from the spawn with the main landmass
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeleportConfig {
+    /// Islands with fewer tiles than this are left unlinked
+    min_island_size: usize,
+}
+
+impl Default for TeleportConfig {
+    fn default() -> Self {
+        Self { min_island_size: 16 }
+    }
+}
+
+impl TeleportConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_min_island_size(mut self, min_island_size: usize) -> Self {
+        self.min_island_size = min_island_size;
+        self
+    }
+
+    /// An island has at least one tile
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        if self.min_island_size == 0 {
+            return Err(GeneratorError::InvalidParameter("teleports.min_island_size".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Label the 4-connected components of walkable tiles, returns the label map and the tiles of every component
+fn components(map: &[Vec<Tile>]) -> (Vec<Vec<Option<usize>>>, Vec<Vec<Vector2>>) {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let mut labels = vec![vec![None; height]; width];
+    let mut components = Vec::<Vec<Vector2>>::new();
+
+    for x in 0..width {
+        for y in 0..height {
+            if labels[x][y].is_some() || !map[x][y].tile_type.properties().walk() {
+                continue;
+            }
+            let label = components.len();
+            let mut component = Vec::<Vector2>::new();
+            let mut queue = VecDeque::from([Vector2::new(x, y)]);
+            labels[x][y] = Some(label);
+            while let Some(p) = queue.pop_front() {
+                component.push(p);
+                for n in p.neighbours(width, height) {
+                    if labels[n.x][n.y].is_none() && map[n.x][n.y].tile_type.properties().walk() {
+                        labels[n.x][n.y] = Some(label);
+                        queue.push_back(n);
+                    }
+                }
+            }
+            components.push(component);
+        }
+    }
+    (labels, components)
+}
+
+/// Link every island that can't be reached from the spawn to the main landmass
+/// (the component of the spawn) with a pair of Teleport(false) tiles.
+/// A multi-source BFS from the main landmass finds, for every tile, the closest tile of it:
+/// every island gets its teleporter on its tile closest to the main landmass,
+/// the other end is that closest tile.
+/// Returns the pairs as (main landmass, island).
+pub(crate) fn link_islands<R: Rng>(map: &mut [Vec<Tile>], spawn: Vector2, config: &TeleportConfig, rng: &mut R) -> Vec<(Vector2, Vector2)> {
+    let (width, height) = (map.len(), map.first().map_or(0, |c| c.len()));
+    let (labels, components) = components(map);
+    let Some(main) = labels[spawn.x][spawn.y] else {
+        return Vec::new();
+    };
+
+    // (closest main landmass tile, distance) of every tile of the map
+    let mut closest: Vec<Vec<Option<(Vector2, usize)>>> = vec![vec![None; height]; width];
+    let mut queue = VecDeque::new();
+    for p in components[main].iter() {
+        closest[p.x][p.y] = Some((*p, 0));
+        queue.push_back(*p);
+    }
+    while let Some(p) = queue.pop_front() {
+        let (origin, distance) = closest[p.x][p.y].unwrap();
+        for n in p.neighbours(width, height) {
+            if closest[n.x][n.y].is_none() {
+                closest[n.x][n.y] = Some((origin, distance + 1));
+                queue.push_back(n);
+            }
+        }
+    }
+
+    let mut used = vec![vec![false; height]; width];
+    used[spawn.x][spawn.y] = true;
+    let mut pairs = Vec::<(Vector2, Vector2)>::new();
+    for (label, island) in components.iter().enumerate() {
+        if label == main || island.len() < config.min_island_size {
+            continue;
+        }
+        // Every tile is reached by the BFS, the map is a single grid
+        let Some(near) = island.iter().min_by_key(|p| closest[p.x][p.y].map_or(usize::MAX, |(_, d)| d)) else {
+            continue;
+        };
+        let mut landing = closest[near.x][near.y].unwrap().0;
+        // Two islands sharing the same closest tile, any free tile of the main landmass will do
+        if used[landing.x][landing.y] {
+            let free: Vec<&Vector2> = components[main].iter().filter(|p| !used[p.x][p.y]).collect();
+            match free.choose(rng) {
+                Some(p) => landing = **p,
+                None => break,
+            }
+        }
+
+        for p in [landing, *near] {
+            used[p.x][p.y] = true;
+            map[p.x][p.y].tile_type = TileType::Teleport(false);
+            map[p.x][p.y].content = Content::None;
+        }
+        pairs.push((landing, *near));
+    }
+    pairs
+}
@@ -0,0 +1,68 @@
+use noise::{NoiseFn, Perlin};
+
+use crate::utils::generator_error::GeneratorError;
+
+/// A layer of the domain warp: the point p is moved to p + strength * (wx(p * frequency), wy(p * frequency)),
+/// wx and wy two Perlin noises in [-1, 1]. Both are in noise units, the ones of NoiseBundle.scale
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarpLayer {
+    /// Maximum displacement of the point
+    strength: f64,
+    /// Frequency of the warp noises, low values bend the continents, high ones twist the coasts
+    frequency: f64,
+}
+
+/// Warp of the coordinates before the noise_fn is sampled, for swirly continents and twisted ranges.
+/// The layers are applied in order, each one to the point already moved by the previous ones.
+/// The warp only moves the sampled point: the bounds of the noise_fn still hold
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DomainWarp {
+    layers: Vec<WarpLayer>,
+}
+
+impl DomainWarp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_layer(mut self, strength: f64, frequency: f64) -> Self {
+        self.layers.push(WarpLayer { strength, frequency });
+        self
+    }
+
+    /// Every layer has a finite strength >= 0 and a finite frequency > 0
+    pub fn is_valid(&self) -> Result<(), GeneratorError> {
+        let valid = |layer: &WarpLayer| {
+            layer.strength.is_finite() && layer.strength >= 0.0 && layer.frequency.is_finite() && layer.frequency > 0.0
+        };
+        if !self.layers.iter().all(valid) {
+            return Err(GeneratorError::InvalidParameter("domain_warp".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The noises of the i-th layer are seeded with seed + 100 + 2i (x) and seed + 101 + 2i (y),
+    /// above the offsets of the biome and clustering noises
+    pub(crate) fn sampler(&self, seed: u32) -> WarpSampler {
+        WarpSampler {
+            layers: self.layers.iter().enumerate().map(|(i, layer)| {
+                let i = i as u32;
+                (*layer, Perlin::new(seed.wrapping_add(100 + 2 * i)), Perlin::new(seed.wrapping_add(101 + 2 * i)))
+            }).collect(),
+        }
+    }
+}
+
+pub(crate) struct WarpSampler {
+    layers: Vec<(WarpLayer, Perlin, Perlin)>,
+}
+
+impl WarpSampler {
+    /// Warped point, in noise units
+    pub(crate) fn warp(&self, point: [f64; 2]) -> [f64; 2] {
+        self.layers.iter().fold(point, |[x, y], (layer, wx, wy)| {
+            let p = [x * layer.frequency, y * layer.frequency];
+            [x + layer.strength * wx.get(p), y + layer.strength * wy.get(p)]
+        })
+    }
+}